@@ -1,34 +1,40 @@
 use axum::{Router, extract::State, routing::get};
 use tokio::sync::mpsc::Receiver;
-use uni_service::{BaseService, run_service};
+use uni_service::{AsyncBaseService, AsyncServiceApp as _, ServiceContext, run_service_async};
 
 // *** AxumServer ***
 
 struct AxumServer {
-    shutdown: Option<Receiver<()>>,
-    is_service: bool,
+    context: ServiceContext<Receiver<()>>,
 }
 
 impl AxumServer {
-    fn new(receiver: Receiver<()>, is_service: bool) -> Self {
-        Self {
-            shutdown: Some(receiver),
-            is_service,
-        }
+    fn new(context: ServiceContext<Receiver<()>>) -> Self {
+        Self { context }
     }
 
-    #[tokio::main(flavor = "current_thread")]
     async fn run_server(&mut self) -> uni_service::Result<()> {
         let app = Router::new()
             .route("/", get(Self::root))
-            .with_state(self.is_service);
+            .with_state(self.context.is_service());
 
-        let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
-        let receiver = std::mem::take(&mut self.shutdown).ok_or("Receiver not found")?;
+        // Use the socket passed in by systemd (`FileDescriptorName=http`), if any, else bind our own
+        let listener = match self.context.listen_fds().take_tcp_listener("http")? {
+            Some(listener) => {
+                listener.set_nonblocking(true)?;
+                tokio::net::TcpListener::from_std(listener)?
+            }
+            None => tokio::net::TcpListener::bind("0.0.0.0:8000").await?,
+        };
+        // The shutdown token is cancelled at the same time the shutdown receiver gets its message
+        let shutdown = self.context.shutdown_token().cancelled();
 
-        tracing::info!("Serving on port 8000...");
+        tracing::info!("Serving on {}...", listener.local_addr()?);
         axum::serve(listener, app)
-            .with_graceful_shutdown(Self::wait_for_shutdown(receiver))
+            .with_graceful_shutdown(async move {
+                shutdown.await;
+                tracing::info!("Shutdown signal received. Shutting down...");
+            })
             .await?;
         Ok(())
     }
@@ -40,38 +46,40 @@ impl AxumServer {
             "Hello, World! (interactive mode)"
         }
     }
+}
 
-    async fn wait_for_shutdown(mut receiver: Receiver<()>) {
-        receiver
-            .recv()
-            .await
-            .expect("Could not receive shutdown signal");
-        tracing::info!("Shutdown signal received. Shutting down...");
+// *** Main ***
+
+// Logs go to the journal when run as a systemd service, else to stderr
+#[cfg(feature = "tracing-subscriber")]
+fn init_logging(name: &str, service_mode: bool) {
+    if let Err(e) = uni_service::ServiceLogger::new(name, service_mode).init() {
+        eprintln!("Could not set up logging: {e}");
     }
 }
 
-// *** Main ***
+#[cfg(not(feature = "tracing-subscriber"))]
+fn init_logging(_name: &str, _service_mode: bool) {
+    tracing_subscriber::fmt().with_target(false).init();
+}
 
-fn run() -> uni_service::Result<()> {
-    let service_mode = match std::env::args().nth(1).as_deref() {
-        Some("service") => true,
-        _ => false,
-    };
+async fn run() -> uni_service::Result<()> {
+    let service_mode = matches!(std::env::args().nth(1).as_deref(), Some("service"));
 
-    let axum_service = |shutdown: Receiver<()>, is_service: bool| -> uni_service::Result<()> {
-        let mut server = AxumServer::new(shutdown, is_service);
-        server.run_server()
+    let axum_service = async |_shutdown: Receiver<()>, context: ServiceContext<Receiver<()>>| {
+        let mut server = AxumServer::new(context);
+        server.run_server().await
     };
-    let service = BaseService::new_tokio("axum_service", axum_service, service_mode);
-    run_service(service, service_mode)?;
+    let service = AsyncBaseService::new("axum_service", axum_service, service_mode);
+    init_logging(service.name(), service_mode);
+    run_service_async(service, service_mode).await?;
     Ok(())
 }
 
-fn main() {
-    tracing_subscriber::fmt().with_target(false).init();
-
-    if let Err(e) = run() {
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    if let Err(e) = run().await {
         tracing::error!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(uni_service::exit_code(&*e));
     }
 }
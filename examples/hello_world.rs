@@ -1,9 +1,12 @@
 use std::sync::mpsc::Receiver;
 
-use uni_service::{BaseService, run_service};
+use uni_service::{BaseService, ServiceApp as _, ServiceContext, detect_service_mode, run_service};
 
-fn hello_service(shutdown: Receiver<()>, is_service: bool) -> uni_service::Result<()> {
-    if is_service {
+fn hello_service(
+    shutdown: Receiver<()>,
+    context: ServiceContext<Receiver<()>>,
+) -> uni_service::Result<()> {
+    if context.is_service() {
         tracing::info!("Hello, World! (service mode)");
     } else {
         tracing::info!("Hello, World! (interactive mode)");
@@ -13,19 +16,33 @@ fn hello_service(shutdown: Receiver<()>, is_service: bool) -> uni_service::Resul
     Ok(())
 }
 
+// Logs go to the journal when run as a systemd service, else to stderr
+#[cfg(feature = "tracing-subscriber")]
+fn init_logging(name: &str, service_mode: bool) {
+    if let Err(e) = uni_service::ServiceLogger::new(name, service_mode).init() {
+        eprintln!("Could not set up logging: {e}");
+    }
+}
+
+#[cfg(not(feature = "tracing-subscriber"))]
+fn init_logging(_name: &str, _service_mode: bool) {
+    tracing_subscriber::fmt().with_target(false).init();
+}
+
 fn run() -> uni_service::Result<()> {
-    let service_mode = matches!(std::env::args().nth(1).as_deref(), Some("service"));
+    // A "service" argument forces service mode, otherwise it is detected
+    let service_mode =
+        matches!(std::env::args().nth(1).as_deref(), Some("service")) || detect_service_mode();
     let service = BaseService::new_sync("hello_world", hello_service, service_mode);
+    init_logging(service.name(), service_mode);
 
     run_service(service, service_mode)?;
     Ok(())
 }
 
 fn main() {
-    tracing_subscriber::fmt().with_target(false).init();
-
     if let Err(e) = run() {
         tracing::error!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(uni_service::exit_code(&*e));
     }
 }
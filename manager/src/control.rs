@@ -0,0 +1,167 @@
+use std::{
+    env,
+    ffi::OsStr,
+    io::{BufRead as _, BufReader, Write as _},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use uni_error::*;
+
+use crate::{ServiceErrKind, ServiceStatus};
+
+// Long enough for the service to answer while it is busy (for example, reloading)
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The health of a service, as reported by the service itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AppHealth {
+    /// Working as expected.
+    Ok,
+    /// Working, but with reduced capacity or performance.
+    Degraded,
+    /// Not doing its work, even though it is still running.
+    Failing,
+}
+
+/// The live state of a service, as reported by the service itself over its control socket.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppStatus {
+    /// Either `Running` or `Paused`.
+    pub status: ServiceStatus,
+    /// The overall health of the service.
+    pub health: AppHealth,
+    /// The full health report, with the components that are not ok (for example,
+    /// `degraded: database (replica lag is 30s)`).
+    pub health_report: String,
+}
+
+/// A client for the control socket of a running `uni_service` service (enabled in the service with
+/// `RunOptions::with_control_socket`). It is only available on Unix.
+pub struct ControlClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl ControlClient {
+    /// Connects to the control socket at `path`. It returns an error if the service is not running or was not
+    /// set up with a control socket.
+    pub fn connect(path: impl AsRef<Path>) -> UniResult<Self, ServiceErrKind> {
+        let writer = UnixStream::connect(path).kind(ServiceErrKind::IoError)?;
+        writer
+            .set_read_timeout(Some(CONTROL_TIMEOUT))
+            .kind(ServiceErrKind::IoError)?;
+        let reader = BufReader::new(writer.try_clone().kind(ServiceErrKind::IoError)?);
+        Ok(Self { reader, writer })
+    }
+
+    /// Sends a raw request line (`status`, `health`, `reload`, `pause`, `resume`, `stop` or `command <number>`)
+    /// and returns the value of the response (empty if it has none). It returns an error if the service
+    /// rejects the request.
+    pub fn request(&mut self, request: &str) -> UniResult<String, ServiceErrKind> {
+        writeln!(self.writer, "{request}").kind(ServiceErrKind::IoError)?;
+        let mut response = String::new();
+        if self
+            .reader
+            .read_line(&mut response)
+            .kind(ServiceErrKind::IoError)?
+            == 0
+        {
+            return Err(UniError::from_kind_context(
+                ServiceErrKind::IoError,
+                "The control socket was closed",
+            ));
+        }
+
+        let response = response.trim_end();
+        match response.split_once(' ').unwrap_or((response, "")) {
+            ("ok", value) => Ok(value.to_string()),
+            ("error", message) => {
+                Err(ServiceErrKind::ControlRequestFailed(message.to_string()).into_error())
+            }
+            _ => Err(UniError::from_kind_context(
+                ServiceErrKind::ControlRequestFailed(response.to_string()),
+                "Unexpected response from the control socket",
+            )),
+        }
+    }
+
+    /// Gets the live status and health of the service.
+    pub fn app_status(&mut self) -> UniResult<AppStatus, ServiceErrKind> {
+        let status = match self.request("status")?.as_str() {
+            "paused" => ServiceStatus::Paused,
+            _ => ServiceStatus::Running,
+        };
+        let health_report = self.request("health")?;
+        let health = match health_report.split(':').next() {
+            Some("ok") => AppHealth::Ok,
+            Some("degraded") => AppHealth::Degraded,
+            Some("failing") => AppHealth::Failing,
+            _ => {
+                return Err(UniError::from_kind_context(
+                    ServiceErrKind::ControlRequestFailed(health_report),
+                    "Unexpected health report from the control socket",
+                ));
+            }
+        };
+        Ok(AppStatus {
+            status,
+            health,
+            health_report,
+        })
+    }
+
+    /// Asks the service to reload its configuration.
+    pub fn reload(&mut self) -> UniResult<(), ServiceErrKind> {
+        self.request("reload").map(|_| ())
+    }
+
+    /// Asks the service to pause.
+    pub fn pause(&mut self) -> UniResult<(), ServiceErrKind> {
+        self.request("pause").map(|_| ())
+    }
+
+    /// Asks the paused service to resume.
+    pub fn resume(&mut self) -> UniResult<(), ServiceErrKind> {
+        self.request("resume").map(|_| ())
+    }
+
+    /// Asks the service to stop. Unlike [`UniServiceManager::stop`](crate::UniServiceManager::stop), it also
+    /// works for services run outside of the service manager. The service may still be stopping when it returns.
+    pub fn stop(&mut self) -> UniResult<(), ServiceErrKind> {
+        self.request("stop").map(|_| ())
+    }
+
+    /// Sends an application-defined command to the service.
+    pub fn send_command(&mut self, command: u32) -> UniResult<(), ServiceErrKind> {
+        self.request(&format!("command {command}")).map(|_| ())
+    }
+}
+
+// Must match where `uni_service` puts the socket: system services run as root, and user services get
+// `XDG_RUNTIME_DIR` from the service manager (when supported)
+pub(crate) fn control_socket_path(name: &OsStr, user: bool) -> PathBuf {
+    let dir = if user {
+        env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(env::temp_dir)
+    } else if Path::new("/run").is_dir() {
+        PathBuf::from("/run")
+    } else {
+        PathBuf::from("/var/run")
+    };
+
+    // Escaped like `uni_service` does, so the socket is always in the directory
+    let file_name: String = name
+        .to_string_lossy()
+        .chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            '/' | '\\' | '\0' => '_',
+            '.' if i == 0 => '_',
+            c => c,
+        })
+        .collect();
+    dir.join(format!("{file_name}.sock"))
+}
@@ -1,5 +1,7 @@
 //! Service management crate which gives a unified interface, but is platform dependent
 
+#[cfg(unix)]
+mod control;
 #[cfg(target_os = "macos")]
 mod launchd;
 mod manager;
@@ -14,4 +16,6 @@ mod unix_util;
 #[cfg(not(target_os = "windows"))]
 mod util;
 
+#[cfg(unix)]
+pub use control::{AppHealth, AppStatus, ControlClient};
 pub use manager::*;
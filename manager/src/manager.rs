@@ -187,10 +187,7 @@ impl ServiceSpec {
 
     pub(crate) fn path_and_args(&self) -> Vec<&OsStr> {
         let mut result = vec![self.path.as_ref()];
-        let args = self
-            .args
-            .iter()
-            .map(|arg| <OsString as AsRef<OsStr>>::as_ref(arg));
+        let args = self.args.iter().map(<OsString as AsRef<OsStr>>::as_ref);
         result.extend(args);
         result
     }
@@ -198,17 +195,14 @@ impl ServiceSpec {
     #[cfg(not(target_os = "windows"))]
     pub(crate) fn path_and_args_string(&self) -> UniResult<Vec<String>, ServiceErrKind> {
         let combined = self.path_and_args();
-        combined
-            .iter()
-            .map(|arg| util::os_string_to_string(arg))
-            .collect()
+        combined.iter().map(util::os_string_to_string).collect()
     }
 
     #[cfg(target_os = "linux")]
     pub(crate) fn description_string(&self) -> UniResult<Option<String>, ServiceErrKind> {
         self.description
             .as_ref()
-            .map(|desc| util::os_string_to_string(desc))
+            .map(util::os_string_to_string)
             .transpose()
     }
 
@@ -216,7 +210,7 @@ impl ServiceSpec {
     pub(crate) fn user_string(&self) -> UniResult<Option<String>, ServiceErrKind> {
         self.user
             .as_ref()
-            .map(|user| util::os_string_to_string(user))
+            .map(util::os_string_to_string)
             .transpose()
     }
 
@@ -224,7 +218,7 @@ impl ServiceSpec {
     pub(crate) fn group_string(&self) -> UniResult<Option<String>, ServiceErrKind> {
         self.group
             .as_ref()
-            .map(|group| util::os_string_to_string(group))
+            .map(util::os_string_to_string)
             .transpose()
     }
 }
@@ -277,8 +271,9 @@ pub(crate) trait ServiceManager {
     fn status(&self) -> UniResult<ServiceStatus, ServiceErrKind>;
 }
 
-/// The error type for service management operations.
+/// The error type for service management operations. New kinds of errors may be added in future releases.
 #[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum ServiceErrKind {
     /// Service management is not available on this platform either because it's not
     /// supported or because the service manager is not detected.
@@ -313,6 +308,8 @@ pub enum ServiceErrKind {
     BadSid,
     /// The operation failed because of a platform-specific error.
     PlatformError(Option<i64>),
+    /// The service rejected a request sent over its control socket. The message from the service is returned.
+    ControlRequestFailed(String),
 
     /// The operation failed because of an unknown error.
     Unknown,
@@ -355,6 +352,9 @@ impl UniKind for ServiceErrKind {
             ServiceErrKind::PlatformError(code) => {
                 format!("A platform-specific error occurred. Code: {:?}", code).into()
             }
+            ServiceErrKind::ControlRequestFailed(msg) => {
+                format!("The service rejected the control request: {msg}").into()
+            }
             ServiceErrKind::Unknown => "Unknown error".into(),
         })
     }
@@ -367,6 +367,8 @@ impl UniKind for ServiceErrKind {
 /// of the platform.
 pub struct UniServiceManager {
     manager: Box<dyn ServiceManager>,
+    #[cfg(unix)]
+    name: OsString,
 }
 
 impl UniServiceManager {
@@ -387,7 +389,11 @@ impl UniServiceManager {
                 "The service name cannot be empty",
             ));
         }
-        make_service_manager(name, prefix.into(), user).map(|manager| Self { manager })
+        make_service_manager(name.clone(), prefix.into(), user).map(|manager| Self {
+            manager,
+            #[cfg(unix)]
+            name,
+        })
     }
 
     /// Gets the capabilities of the underlying platform service manager.
@@ -600,6 +606,21 @@ impl UniServiceManager {
         self.manager.status()
     }
 
+    /// Gets the path of the control socket of a `uni_service` service (see [`control_client`](Self::control_client)).
+    /// The service must be named the same as here, and run as root if it is a system service.
+    #[cfg(unix)]
+    pub fn control_socket_path(&self) -> PathBuf {
+        crate::control::control_socket_path(&self.name, self.is_user_service())
+    }
+
+    /// Connects to the control socket of a running `uni_service` service, to query the state it reports
+    /// (which is more detailed than [`status`](Self::status)) or control it. The service must be set up with
+    /// a control socket at the default path. It is only available on Unix.
+    #[cfg(unix)]
+    pub fn control_client(&self) -> UniResult<crate::ControlClient, ServiceErrKind> {
+        crate::ControlClient::connect(self.control_socket_path())
+    }
+
     /// Waits for the service to reach the desired status. It returns an error if the service is not installed
     /// the status cannot be determined, or if the service does not reach the desired status before the timeout.
     pub fn wait_for_status(
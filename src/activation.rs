@@ -0,0 +1,206 @@
+use std::net::{TcpListener, UdpSocket};
+#[cfg(unix)]
+use std::{
+    env, io, mem,
+    os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd, RawFd},
+    os::unix::net::UnixListener,
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::Result;
+
+#[cfg(unix)]
+const LISTEN_FDS_ENV: &str = "LISTEN_FDS";
+#[cfg(unix)]
+const LISTEN_PID_ENV: &str = "LISTEN_PID";
+#[cfg(unix)]
+const LISTEN_FDNAMES_ENV: &str = "LISTEN_FDNAMES";
+#[cfg(unix)]
+const LISTEN_FDS_START: RawFd = 3;
+#[cfg(unix)]
+const UNKNOWN_NAME: &str = "unknown";
+
+// The passed sockets can only be owned once per process
+#[cfg(unix)]
+static FDS_TAKEN: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+#[derive(Clone, Copy, PartialEq)]
+enum SocketKind {
+    Tcp,
+    Udp,
+    Unix,
+}
+
+#[cfg(unix)]
+impl SocketKind {
+    fn description(self) -> &'static str {
+        match self {
+            SocketKind::Tcp => "a TCP listener",
+            SocketKind::Udp => "a UDP socket",
+            SocketKind::Unix => "a Unix stream listener",
+        }
+    }
+}
+
+/// Sockets passed to the process by the service manager (systemd socket activation). Sockets are looked up
+/// by the name given to them in the socket unit (`FileDescriptorName=`), which defaults to the name of
+/// the socket unit. Sockets without a name are named `unknown`. On platforms without socket activation
+/// this is always empty.
+#[derive(Default)]
+pub struct ListenFds {
+    #[cfg(unix)]
+    fds: Vec<(String, OwnedFd)>,
+}
+
+#[cfg(unix)]
+impl ListenFds {
+    /// Takes ownership of the sockets passed in via `LISTEN_FDS`, `LISTEN_PID` and `LISTEN_FDNAMES`. The
+    /// sockets can only be taken once per process, so subsequent calls return an empty set, as does a
+    /// process the sockets were not meant for (including one with a missing or malformed `LISTEN_PID`).
+    ///
+    /// The variables are left in the environment, as modifying it is not safe once other threads exist.
+    /// Child processes inherit them, but ignore them as `LISTEN_PID` does not match their own PID.
+    pub fn from_env() -> Result<Self> {
+        // Like `sd_listen_fds(1)`, anything other than our own PID means the sockets aren't ours
+        match env::var(LISTEN_PID_ENV) {
+            Ok(pid) if pid.parse::<u32>().ok() == Some(process::id()) => {}
+            _ => return Ok(Self::default()),
+        }
+
+        let count = env::var(LISTEN_FDS_ENV)?.parse::<RawFd>()?;
+        let end = LISTEN_FDS_START
+            .checked_add(count)
+            .filter(|_| count >= 0)
+            .ok_or_else(|| format!("Invalid socket count in {LISTEN_FDS_ENV}: {count}"))?;
+        let names = env::var(LISTEN_FDNAMES_ENV).unwrap_or_default();
+        let mut names = names.split(':').filter(|name| !name.is_empty());
+
+        if FDS_TAKEN.swap(true, Ordering::SeqCst) {
+            return Ok(Self::default());
+        }
+
+        let fds = (LISTEN_FDS_START..end)
+            .map(|fd| {
+                // SAFETY: The service manager passed us these descriptors and the flag above guarantees
+                // they are only taken once
+                let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+                // Don't leak the sockets into child processes
+                if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+                    return Err(io::Error::last_os_error().into());
+                }
+
+                let name = names.next().unwrap_or(UNKNOWN_NAME).to_string();
+                tracing::debug!("Received socket '{name}' from the service manager");
+                Ok((name, fd))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { fds })
+    }
+
+    /// Returns `true` if there are no (remaining) sockets.
+    pub fn is_empty(&self) -> bool {
+        self.fds.is_empty()
+    }
+
+    /// Returns the names of the remaining sockets (names may repeat if several sockets share a name).
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fds.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Takes the first TCP listener named `name`, if any. An error is returned if a socket with that name
+    /// exists, but it is not a TCP listener.
+    pub fn take_tcp_listener(&mut self, name: &str) -> Result<Option<TcpListener>> {
+        Ok(self.take(name, SocketKind::Tcp)?.map(TcpListener::from))
+    }
+
+    /// Takes the first UDP socket named `name`, if any. An error is returned if a socket with that name
+    /// exists, but it is not a UDP socket.
+    pub fn take_udp_socket(&mut self, name: &str) -> Result<Option<UdpSocket>> {
+        Ok(self.take(name, SocketKind::Udp)?.map(UdpSocket::from))
+    }
+
+    /// Takes the first Unix stream listener named `name`, if any. An error is returned if a socket with that
+    /// name exists, but it is not a Unix stream listener.
+    pub fn take_unix_listener(&mut self, name: &str) -> Result<Option<UnixListener>> {
+        Ok(self.take(name, SocketKind::Unix)?.map(UnixListener::from))
+    }
+
+    fn take(&mut self, name: &str, kind: SocketKind) -> Result<Option<OwnedFd>> {
+        let Some(idx) = self.fds.iter().position(|(fd_name, _)| fd_name == name) else {
+            return Ok(None);
+        };
+
+        if socket_kind(&self.fds[idx].1)? != Some(kind) {
+            return Err(format!("Socket '{name}' is not {}", kind.description()).into());
+        }
+        Ok(Some(self.fds.remove(idx).1))
+    }
+}
+
+// Socket activation is a systemd feature, so there is never anything to take
+#[cfg(not(unix))]
+impl ListenFds {
+    /// Returns an empty set of sockets.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self::default())
+    }
+
+    /// Always returns `true`.
+    pub fn is_empty(&self) -> bool {
+        true
+    }
+
+    /// Always returns an empty iterator.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::empty()
+    }
+
+    /// Always returns `None`.
+    pub fn take_tcp_listener(&mut self, _name: &str) -> Result<Option<TcpListener>> {
+        Ok(None)
+    }
+
+    /// Always returns `None`.
+    pub fn take_udp_socket(&mut self, _name: &str) -> Result<Option<UdpSocket>> {
+        Ok(None)
+    }
+}
+
+#[cfg(unix)]
+fn socket_kind(fd: &OwnedFd) -> Result<Option<SocketKind>> {
+    let fd = fd.as_raw_fd();
+
+    let mut sock_type: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    // SAFETY: The buffer and length are valid for the type of the option
+    let res = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut sock_type as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    // SAFETY: An all zero `sockaddr_storage` is valid, and it is large enough for any address
+    let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let res =
+        unsafe { libc::getsockname(fd, &mut addr as *mut _ as *mut libc::sockaddr, &mut len) };
+    if res < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(match (addr.ss_family as libc::c_int, sock_type) {
+        (libc::AF_INET | libc::AF_INET6, libc::SOCK_STREAM) => Some(SocketKind::Tcp),
+        (libc::AF_INET | libc::AF_INET6, libc::SOCK_DGRAM) => Some(SocketKind::Udp),
+        (libc::AF_UNIX, libc::SOCK_STREAM) => Some(SocketKind::Unix),
+        _ => None,
+    })
+}
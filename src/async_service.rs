@@ -0,0 +1,589 @@
+use std::{future::Future, mem, ops::ControlFlow, path::PathBuf, process, time::Duration};
+
+#[cfg(windows)]
+use tokio::runtime::Handle;
+use tokio::{
+    sync::mpsc::{Receiver, Sender, channel, unbounded_channel},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+use crate::base::HealthFn;
+use crate::notify;
+use crate::panic::CatchPanic;
+use crate::run_loop::{AppCall, RunLoop, Step};
+use crate::{
+    ControlEvent, ControlSender, ExitNotifier, HealthReport, HealthState, ListenFds, Result,
+    RunOptions, STOP_TIMEOUT_EXIT_CODE, ServiceContext, ShutdownReason, ShutdownToken,
+    attach_control_sources, stop_result,
+};
+#[cfg(windows)]
+use crate::{ServiceApp, start_service};
+
+/// An asynchronous service application. Unlike [`ServiceApp`](crate::ServiceApp), it runs directly on an
+/// existing `tokio` runtime via [`run_service_async`] without requiring a dedicated thread.
+pub trait AsyncServiceApp {
+    /// Returns the name of the service.
+    fn name(&self) -> &str;
+
+    /// Called when the service is started. It should do its work as
+    /// quickly as possible and return (typically by spawning a task).
+    fn start(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Called when the service is stopped. It should do any cleanup necessary and return.
+    fn stop(self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Called instead of [`stop`](Self::stop) when the runtime stops the service.
+    /// See [`ServiceApp::stop_with_reason`](crate::ServiceApp::stop_with_reason).
+    fn stop_with_reason(self, reason: ShutdownReason) -> impl Future<Output = Result<()>> + Send
+    where
+        Self: Sized,
+    {
+        let _ = reason;
+        self.stop()
+    }
+
+    /// Returns whether the service is currently running. If it returns `false`, the service
+    /// itself will be stopped.
+    fn is_running(&self) -> bool;
+
+    /// Called before [`start`](Self::start) with a notifier the app should use to report that it stopped
+    /// running on its own. See [`ServiceApp::set_exit_notifier`](crate::ServiceApp::set_exit_notifier).
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        let _ = notifier;
+        false
+    }
+
+    /// Returns whether the service is healthy. See [`ServiceApp::is_healthy`](crate::ServiceApp::is_healthy).
+    fn is_healthy(&self) -> bool {
+        true
+    }
+
+    /// Returns a report of the health of the service. See [`ServiceApp::health`](crate::ServiceApp::health).
+    fn health(&self) -> HealthReport {
+        match self.is_healthy() {
+            true => HealthReport::ok(),
+            false => HealthReport::new(HealthState::Failing),
+        }
+    }
+
+    /// Returns how long the service may take to stop, if known.
+    /// See [`ServiceApp::stop_timeout`](crate::ServiceApp::stop_timeout).
+    fn stop_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called when the service is asked to reload its configuration.
+    /// See [`ServiceApp::reload`](crate::ServiceApp::reload).
+    fn reload(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Returns whether the service supports being paused.
+    /// See [`ServiceApp::can_pause`](crate::ServiceApp::can_pause).
+    fn can_pause(&self) -> bool {
+        false
+    }
+
+    /// Called when the service is asked to pause. See [`ServiceApp::pause`](crate::ServiceApp::pause).
+    fn pause(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Called when a paused service is asked to resume. See [`ServiceApp::resume`](crate::ServiceApp::resume).
+    fn resume(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+
+    /// Called when the service is sent an application-defined command.
+    /// See [`ServiceApp::command`](crate::ServiceApp::command).
+    fn command(&mut self, command: u32) -> impl Future<Output = Result<()>> + Send {
+        // The app itself may not be `Send`, so the future only captures its name
+        let name = self.name().to_string();
+        async move {
+            tracing::warn!("Service '{name}' does not handle command {command}");
+            Ok(())
+        }
+    }
+}
+
+/// Executes an asynchronous service on the current `tokio` runtime. If being started by the service manager,
+/// `service_mode` must be `true`. If being started interactively, `service_mode` must be `false`.
+pub async fn run_service_async(
+    app: impl AsyncServiceApp + Send + 'static,
+    service_mode: bool,
+) -> Result<()> {
+    run_service_async_with(app, service_mode, RunOptions::default()).await
+}
+
+/// Executes an asynchronous service like [`run_service_async`], with the given options (see
+/// [`run_service_with`](crate::run_service_with)).
+pub async fn run_service_async_with(
+    app: impl AsyncServiceApp + Send + 'static,
+    service_mode: bool,
+    options: RunOptions,
+) -> Result<()> {
+    match service_mode {
+        #[cfg(windows)]
+        true => {
+            // The Windows service dispatcher blocks, so it gets its own thread, and the app is driven from
+            // there on our runtime
+            let app = Box::new(BlockingApp {
+                app: Some(app),
+                runtime: Handle::current(),
+            });
+            tokio::task::spawn_blocking(move || start_service(app, options)).await?
+        }
+        // Outside of Windows, services run as they would interactively, except they have no console
+        #[cfg(not(windows))]
+        true => run_interactive(app, &options.with_console(false)).await,
+        false => run_interactive(app, &options).await,
+    }
+}
+
+async fn run_interactive(mut app: impl AsyncServiceApp, options: &RunOptions) -> Result<()> {
+    let (control_tx, mut control_rx) = unbounded_channel();
+    let _sources = attach_control_sources(
+        app.name(),
+        ControlSender::Async(control_tx.clone()),
+        options,
+    )?;
+    let notifies_exit = app.set_exit_notifier(ExitNotifier::new(move || {
+        // If the receiver is gone, the runtime is no longer waiting
+        let _ = control_tx.send(ControlEvent::AppExited);
+    }));
+
+    app.start().await?;
+    notify::notify_ready();
+
+    // Wait for termination signal or service to exit. If the app notifies us when it exits, we only
+    // wake up for control events (and health checks and watchdog pings), otherwise we also poll whether it
+    // is still running.
+    let mut run_loop = RunLoop::new(options, notifies_exit, |_| {});
+    let mut reason = ShutdownReason::AppExited;
+    while app.is_running() {
+        let timeout = match run_loop.before_wait(app.name(), || app.health()) {
+            ControlFlow::Continue(timeout) => timeout,
+            ControlFlow::Break(stop_reason) => {
+                reason = stop_reason;
+                break;
+            }
+        };
+        let event = match timeout {
+            Some(timeout) => time::timeout(timeout, control_rx.recv()).await,
+            None => Ok(control_rx.recv().await),
+        };
+        let event = match event {
+            Ok(Some(event)) => event,
+            Ok(None) => return Err("Control channel closed".into()),
+            Err(_) => continue,
+        };
+
+        match run_loop.handle_event(event, app.name(), app.can_pause(), || app.health()) {
+            Step::Call(call) => {
+                let result = match call {
+                    AppCall::Reload => app.reload().await,
+                    AppCall::Pause => app.pause().await,
+                    AppCall::Resume => app.resume().await,
+                    AppCall::Command(command) => app.command(command).await,
+                };
+                run_loop.finish(app.name(), call, result);
+            }
+            Step::Continue => {}
+            Step::Stop(stop_reason) => {
+                reason = stop_reason;
+                break;
+            }
+        }
+    }
+
+    notify::notify_stopping();
+    let name = app.name().to_string();
+    app.stop_with_reason(reason).await?;
+    stop_result(&name, reason)
+}
+
+// *** BlockingApp ***
+
+/// Adapts an asynchronous app for use by the synchronous (thread based) runtime
+#[cfg(windows)]
+struct BlockingApp<A> {
+    app: Option<A>,
+    runtime: Handle,
+}
+
+#[cfg(windows)]
+impl<A: AsyncServiceApp> BlockingApp<A> {
+    fn app(&self) -> &A {
+        self.app.as_ref().expect("App already stopped")
+    }
+
+    fn app_mut(&mut self) -> &mut A {
+        self.app.as_mut().expect("App already stopped")
+    }
+}
+
+#[cfg(windows)]
+impl<A: AsyncServiceApp> ServiceApp for BlockingApp<A> {
+    fn name(&self) -> &str {
+        self.app().name()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let runtime = self.runtime.clone();
+        runtime.block_on(self.app_mut().start())
+    }
+
+    fn stop(self: Box<Self>) -> Result<()> {
+        self.stop_with_reason(ShutdownReason::Programmatic)
+    }
+
+    fn stop_with_reason(mut self: Box<Self>, reason: ShutdownReason) -> Result<()> {
+        let app = self.app.take().expect("App already stopped");
+        self.runtime.block_on(app.stop_with_reason(reason))
+    }
+
+    fn is_running(&self) -> bool {
+        self.app().is_running()
+    }
+
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.app_mut().set_exit_notifier(notifier)
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.app().is_healthy()
+    }
+
+    fn health(&self) -> HealthReport {
+        self.app().health()
+    }
+
+    fn stop_timeout(&self) -> Option<Duration> {
+        self.app().stop_timeout()
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let runtime = self.runtime.clone();
+        runtime.block_on(self.app_mut().reload())
+    }
+
+    fn can_pause(&self) -> bool {
+        self.app().can_pause()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        let runtime = self.runtime.clone();
+        runtime.block_on(self.app_mut().pause())
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        let runtime = self.runtime.clone();
+        runtime.block_on(self.app_mut().resume())
+    }
+
+    fn command(&mut self, command: u32) -> Result<()> {
+        let runtime = self.runtime.clone();
+        runtime.block_on(self.app_mut().command(command))
+    }
+}
+
+// *** AsyncBaseService ***
+
+/// A base service implementation for asynchronous services. The service function is spawned as a task on
+/// the current `tokio` runtime. It is the asynchronous counterpart of [`BaseService`](crate::BaseService).
+pub struct AsyncBaseService<F> {
+    name: String,
+    service_fn: Option<F>,
+    sender: Sender<()>,
+    receiver: Option<Receiver<()>>,
+    reload_sender: Sender<()>,
+    reload_receiver: Option<Receiver<()>>,
+    pause_senders: Option<(Sender<()>, Sender<()>)>,
+    pause_receivers: Option<(Receiver<()>, Receiver<()>)>,
+    command_fn: Option<Box<dyn FnMut(u32) -> Result<()> + Send>>,
+    handle: Option<JoinHandle<Result<()>>>,
+    is_service: bool,
+    health_fn: Option<HealthFn>,
+    shutdown_token: ShutdownToken,
+    stop_warning: Option<Duration>,
+    stop_timeout: Option<Duration>,
+    exit_notifier: Option<ExitNotifier>,
+    crash_report_dir: Option<PathBuf>,
+}
+
+impl<F, Fut> AsyncBaseService<F>
+where
+    F: FnOnce(Receiver<()>, ServiceContext<Receiver<()>>) -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    /// Creates a new asynchronous base service. `service_fn` is the function that will be executed by the
+    /// service, and the future it returns is spawned as a task. A receiver channel will be passed to the service
+    /// function and will receive a message when the service should shutdown. A second channel for reload
+    /// notifications is available via [`ServiceContext::take_reload_receiver`].
+    pub fn new(name: impl Into<String>, service_fn: F, is_service: bool) -> Self {
+        let (sender, receiver) = channel(1);
+        let (reload_sender, reload_receiver) = channel(1);
+
+        Self {
+            name: name.into(),
+            service_fn: Some(service_fn),
+            sender,
+            receiver: Some(receiver),
+            reload_sender,
+            reload_receiver: Some(reload_receiver),
+            pause_senders: None,
+            pause_receivers: None,
+            command_fn: None,
+            handle: None,
+            is_service,
+            health_fn: None,
+            shutdown_token: ShutdownToken::new(),
+            stop_warning: None,
+            stop_timeout: None,
+            exit_notifier: None,
+            crash_report_dir: None,
+        }
+    }
+
+    /// Sets up pause and resume notifications, so the service can be paused. The receivers are made available
+    /// to the service function via [`ServiceContext::take_pause_receiver`] and
+    /// [`ServiceContext::take_resume_receiver`]. See [`BaseService::with_pause`](crate::BaseService::with_pause).
+    pub fn with_pause(mut self) -> Self {
+        let (pause_sender, pause_receiver) = channel(1);
+        let (resume_sender, resume_receiver) = channel(1);
+        self.pause_senders = Some((pause_sender, resume_sender));
+        self.pause_receivers = Some((pause_receiver, resume_receiver));
+        self
+    }
+
+    /// Sets a handler for application-defined commands.
+    /// See [`BaseService::with_command_handler`](crate::BaseService::with_command_handler).
+    pub fn with_command_handler(
+        mut self,
+        command_fn: impl FnMut(u32) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.command_fn = Some(Box::new(command_fn));
+        self
+    }
+
+    /// Sets a health check for the service. See [`BaseService::with_health_check`](crate::BaseService::with_health_check).
+    pub fn with_health_check(self, health_fn: impl Fn() -> bool + Send + 'static) -> Self {
+        self.with_health_report(move || match health_fn() {
+            true => HealthReport::ok(),
+            false => HealthReport::new(HealthState::Failing),
+        })
+    }
+
+    /// Sets a health check for the service that returns a detailed report.
+    /// See [`BaseService::with_health_report`](crate::BaseService::with_health_report).
+    pub fn with_health_report(
+        mut self,
+        health_fn: impl Fn() -> HealthReport + Send + 'static,
+    ) -> Self {
+        self.health_fn = Some(Box::new(health_fn));
+        self
+    }
+
+    /// Sets a soft stop deadline. See [`BaseService::with_stop_warning`](crate::BaseService::with_stop_warning).
+    pub fn with_stop_warning(mut self, warning: Duration) -> Self {
+        self.stop_warning = Some(warning);
+        self
+    }
+
+    /// Sets a hard stop deadline. See [`BaseService::with_stop_timeout`](crate::BaseService::with_stop_timeout).
+    pub fn with_stop_timeout(mut self, timeout: Duration) -> Self {
+        self.stop_timeout = Some(timeout);
+        self
+    }
+
+    /// Writes a crash report to a new file in `dir` if the service function panics.
+    /// See [`BaseService::with_crash_report_dir`](crate::BaseService::with_crash_report_dir).
+    pub fn with_crash_report_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.crash_report_dir = Some(dir.into());
+        self
+    }
+
+    // Takes fields rather than `&self` so the future doesn't require the service function to be `Sync`
+    async fn wait_for_task(
+        name: &str,
+        handle: &mut JoinHandle<Result<()>>,
+        stop_warning: Option<Duration>,
+        stop_timeout: Option<Duration>,
+    ) {
+        let started = Instant::now();
+
+        // A warning due after the timeout would delay it
+        let warning =
+            stop_warning.filter(|warning| stop_timeout.is_none_or(|timeout| *warning < timeout));
+        if let Some(warning) = warning
+            && time::timeout(warning, &mut *handle).await.is_err()
+        {
+            tracing::warn!("Service '{name}' has not stopped after {warning:?}. Still waiting...");
+        }
+
+        if let Some(timeout) = stop_timeout {
+            let remaining = timeout.saturating_sub(started.elapsed());
+            if time::timeout(remaining, &mut *handle).await.is_err() {
+                tracing::error!(
+                    "Service '{name}' did not stop within {timeout:?}. Exiting with code {STOP_TIMEOUT_EXIT_CODE}."
+                );
+                process::exit(STOP_TIMEOUT_EXIT_CODE);
+            }
+        }
+    }
+}
+
+impl<F, Fut> AsyncServiceApp for AsyncBaseService<F>
+where
+    F: FnOnce(Receiver<()>, ServiceContext<Receiver<()>>) -> Fut + Send,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        tracing::info!("Starting service '{}'...", self.name);
+
+        let receiver = mem::take(&mut self.receiver)
+            .ok_or("Receiver not found (service might have been started twice)")?;
+        let context = ServiceContext::new(
+            self.is_service,
+            ListenFds::from_env()?,
+            mem::take(&mut self.reload_receiver),
+            mem::take(&mut self.pause_receivers),
+            self.shutdown_token.clone(),
+            None,
+        );
+        let service_fn = mem::take(&mut self.service_fn).ok_or("Service function not found")?;
+
+        let service_future = CatchPanic::new(
+            self.name.clone(),
+            self.crash_report_dir.clone(),
+            service_fn(receiver, context),
+        );
+        let exit_notifier = self.exit_notifier.clone();
+
+        self.handle = Some(tokio::spawn(async move {
+            // Dropped when the service future completes or panics
+            let _exit_guard = ExitGuard(exit_notifier);
+            service_future.await
+        }));
+        Ok(())
+    }
+
+    async fn stop(self) -> Result<()> {
+        self.stop_with_reason(ShutdownReason::Programmatic).await
+    }
+
+    async fn stop_with_reason(mut self, reason: ShutdownReason) -> Result<()> {
+        self.shutdown_token.cancel_with_reason(reason);
+
+        match mem::take(&mut self.handle) {
+            Some(handle) if handle.is_finished() => {
+                tracing::warn!(
+                    "Service '{}' was already stopped (before we signalled it to do so).",
+                    self.name
+                );
+                // Returns the error returned by the service function (if any), so it can become the exit code
+                handle.await?
+            }
+            Some(mut handle) => {
+                tracing::info!("Stopping service '{}' ({reason})...", self.name);
+                // If the channel is full, shutdown was already signalled. If the receiver is gone, the
+                // service function is relying on the shutdown token instead.
+                let _ = self.sender.try_send(());
+
+                Self::wait_for_task(
+                    &self.name,
+                    &mut handle,
+                    self.stop_warning,
+                    self.stop_timeout,
+                )
+                .await;
+                handle.await??;
+
+                tracing::info!("Service '{}' is shut down.", self.name);
+                Ok(())
+            }
+            None => Err(format!("Task handle not found for service '{}'.", self.name).into()),
+        }
+    }
+
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.exit_notifier = Some(notifier);
+        true
+    }
+
+    fn is_running(&self) -> bool {
+        self.handle
+            .as_ref()
+            .map(|handle| !handle.is_finished())
+            .unwrap_or(false)
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.health().state() != HealthState::Failing
+    }
+
+    fn health(&self) -> HealthReport {
+        self.health_fn
+            .as_ref()
+            .map_or_else(HealthReport::ok, |health_fn| health_fn())
+    }
+
+    fn stop_timeout(&self) -> Option<Duration> {
+        self.stop_timeout
+    }
+
+    async fn reload(&mut self) -> Result<()> {
+        // If the channel is full, a reload is already pending. If the receiver is gone, the
+        // service function isn't interested in reloads.
+        let _ = self.reload_sender.try_send(());
+        Ok(())
+    }
+
+    fn can_pause(&self) -> bool {
+        self.pause_senders.is_some()
+    }
+
+    async fn pause(&mut self) -> Result<()> {
+        // If the channel is full, a pause is already pending. If the receiver is gone, the service
+        // function isn't interested in pausing.
+        if let Some((sender, _)) = &self.pause_senders {
+            let _ = sender.try_send(());
+        }
+        Ok(())
+    }
+
+    async fn resume(&mut self) -> Result<()> {
+        if let Some((_, sender)) = &self.pause_senders {
+            let _ = sender.try_send(());
+        }
+        Ok(())
+    }
+
+    async fn command(&mut self, command: u32) -> Result<()> {
+        match &mut self.command_fn {
+            Some(command_fn) => command_fn(command),
+            None => {
+                tracing::warn!("Service '{}' does not handle command {command}", self.name);
+                Ok(())
+            }
+        }
+    }
+}
+
+// *** ExitGuard ***
+
+/// Notifies the runtime that the service task exited when dropped (including when the task panics)
+struct ExitGuard(Option<ExitNotifier>);
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if let Some(exit_notifier) = &self.0 {
+            exit_notifier.notify();
+        }
+    }
+}
@@ -1,29 +1,65 @@
 use std::{
     mem,
-    sync::mpsc::{Receiver, channel},
+    path::PathBuf,
+    process,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use crate::{Result, ServiceApp};
+use crate::panic::catch_panic;
+use crate::{
+    ExitNotifier, HealthReport, HealthState, ListenFds, RestartPolicy, Result, ServiceApp,
+    ServiceContext, ShutdownReason, ShutdownToken, StartHook,
+};
+
+type SenderFn = Box<dyn Fn() -> Result<()> + Send>;
+type CommandFn = Box<dyn FnMut(u32) -> Result<()> + Send>;
+pub(crate) type HealthFn = Box<dyn Fn() -> HealthReport + Send>;
+
+/// The service function of a [`BaseService`] with a restart policy.
+/// See [`BaseService::with_restart_policy`](BaseService#method.with_restart_policy).
+pub type RestartingFn<R> = Box<dyn FnOnce(R, ServiceContext<R>) -> Result<()> + Send>;
+
+/// The exit code used when a service does not stop before its stop timeout and the process is forcibly
+/// exited. See [`BaseService::with_stop_timeout`].
+pub const STOP_TIMEOUT_EXIT_CODE: i32 = 124;
 
 /// A base service implementation that can be used to build services.
 pub struct BaseService<F, R> {
     name: String,
     service_fn: Option<F>,
-    sender_fn: Box<dyn (Fn() -> Result<()>) + Send>,
+    sender_fn: SenderFn,
     receiver: Option<R>,
     handle: Option<JoinHandle<Result<()>>>,
     is_service: bool,
+    health_fn: Option<HealthFn>,
+    reload_sender_fn: Option<SenderFn>,
+    reload_receiver: Option<R>,
+    pause_sender_fns: Option<(SenderFn, SenderFn)>,
+    pause_receivers: Option<(R, R)>,
+    command_fn: Option<CommandFn>,
+    shutdown_token: ShutdownToken,
+    // Disconnects when the service thread exits (normally or by panicking)
+    done_receiver: Option<Receiver<()>>,
+    exit_notifier: Option<ExitNotifier>,
+    start_hook: Option<StartHook>,
+    stop_warning: Option<Duration>,
+    stop_timeout: Option<Duration>,
+    crash_report_dir: Option<PathBuf>,
 }
 
 impl<F, R> BaseService<F, R>
 where
-    F: FnOnce(R, bool) -> Result<()>,
+    F: FnOnce(R, ServiceContext<R>) -> Result<()>,
 {
     /// Creates a new base service with any custom sender/receiver pair (typically a channel). `service_fn`
     /// is the function that will be executed by the service. `sender_fn` is a function that will be called
     /// to notify the receiver it is time to shutdown the service. `receiver` is the receiver of the shutdown
-    /// notification. The receiver is passed to the service function as a parameter.
+    /// notification. The receiver is passed to the service function as a parameter, along with a [`ServiceContext`].
     pub fn new(
         name: impl Into<String>,
         service_fn: F,
@@ -38,55 +74,400 @@ where
             receiver: Some(receiver),
             handle: None,
             is_service,
+            health_fn: None,
+            reload_sender_fn: None,
+            reload_receiver: None,
+            pause_sender_fns: None,
+            pause_receivers: None,
+            command_fn: None,
+            shutdown_token: ShutdownToken::new(),
+            done_receiver: None,
+            exit_notifier: None,
+            start_hook: None,
+            stop_warning: None,
+            stop_timeout: None,
+            crash_report_dir: None,
         }
     }
 
-    fn join_thread(&self, handle: JoinHandle<Result<()>>) -> Result<()> {
-        if let Err(err) = handle.join().map_err(|_| "Error joining thread")? {
-            tracing::error!("Service '{}' returned an error: {err}", self.name);
+    /// Sets up reload notifications with any custom sender/receiver pair (typically a channel). `sender_fn`
+    /// is a function that will be called each time the service is asked to reload. `receiver` is the
+    /// receiver of the reload notification and is made available to the service function via
+    /// [`ServiceContext::take_reload_receiver`].
+    pub fn with_reload(
+        mut self,
+        sender_fn: impl Fn() -> Result<()> + Send + 'static,
+        receiver: R,
+    ) -> Self {
+        self.reload_sender_fn = Some(Box::new(sender_fn));
+        self.reload_receiver = Some(receiver);
+        self
+    }
+
+    /// Sets up pause and resume notifications with any custom sender/receiver pairs (typically channels), so
+    /// the service can be paused (see [`ServiceApp::pause`]). `pause_sender_fn` and `resume_sender_fn` are
+    /// functions that will be called each time the service is asked to pause and resume. The receivers are
+    /// made available to the service function via [`ServiceContext::take_pause_receiver`] and
+    /// [`ServiceContext::take_resume_receiver`]. A paused service function must still watch for shutdown.
+    pub fn with_pause(
+        mut self,
+        pause_sender_fn: impl Fn() -> Result<()> + Send + 'static,
+        pause_receiver: R,
+        resume_sender_fn: impl Fn() -> Result<()> + Send + 'static,
+        resume_receiver: R,
+    ) -> Self {
+        self.pause_sender_fns = Some((Box::new(pause_sender_fn), Box::new(resume_sender_fn)));
+        self.pause_receivers = Some((pause_receiver, resume_receiver));
+        self
+    }
+
+    /// Sets a handler for application-defined commands (see [`ServiceApp::command`]). `command_fn` is called
+    /// with the command on the runtime thread, so it should only hand the command off to the service function
+    /// (for example, by setting a flag or sending it over a channel) and return.
+    pub fn with_command_handler(
+        mut self,
+        command_fn: impl FnMut(u32) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.command_fn = Some(Box::new(command_fn));
+        self
+    }
+
+    /// Sets a health check for the service. `health_fn` is called periodically while the service is running
+    /// and should return `false` when the service function is hung or otherwise not making progress (for
+    /// example, by checking a heartbeat the service function updates). See [`ServiceApp::is_healthy`].
+    pub fn with_health_check(self, health_fn: impl Fn() -> bool + Send + 'static) -> Self {
+        self.with_health_report(move || match health_fn() {
+            true => HealthReport::ok(),
+            false => HealthReport::new(HealthState::Failing),
+        })
+    }
+
+    /// Sets a health check for the service that returns a detailed report, such as the health of each
+    /// component the service depends on. It replaces any health check set with
+    /// [`with_health_check`](Self::with_health_check). See [`ServiceApp::health`].
+    pub fn with_health_report(
+        mut self,
+        health_fn: impl Fn() -> HealthReport + Send + 'static,
+    ) -> Self {
+        self.health_fn = Some(Box::new(health_fn));
+        self
+    }
+
+    /// Sets a soft stop deadline. If the service function has not returned `warning` after it was signalled
+    /// to shutdown, a warning is logged, but the service is given more time (up to the stop timeout, if any).
+    /// A warning that is not due before the stop timeout is ignored.
+    pub fn with_stop_warning(mut self, warning: Duration) -> Self {
+        self.stop_warning = Some(warning);
+        self
+    }
+
+    /// Sets a hard stop deadline. If the service function has not returned `timeout` after it was signalled
+    /// to shutdown, an error is logged and the process is exited with [`STOP_TIMEOUT_EXIT_CODE`]. Without a
+    /// timeout, a service function that ignores the shutdown signal hangs the process indefinitely. The
+    /// timeout should be shorter than the one used by the service manager (`TimeoutStopSec=` for systemd)
+    /// so the exit code is not lost to the service manager killing the process first.
+    pub fn with_stop_timeout(mut self, timeout: Duration) -> Self {
+        self.stop_timeout = Some(timeout);
+        self
+    }
+
+    /// Writes a crash report (the panic message, location and a backtrace) to a new file in `dir` if the
+    /// service function panics. The directory is created if it does not exist. Panics are always logged and
+    /// returned as a [`PanicError`](crate::PanicError), whether or not a directory is set.
+    pub fn with_crash_report_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.crash_report_dir = Some(dir.into());
+        self
+    }
+
+    fn wait_for_thread(&self) {
+        let Some(done_receiver) = &self.done_receiver else {
+            return;
+        };
+        let started = Instant::now();
+
+        // A warning due after the timeout would delay it
+        let warning = self
+            .stop_warning
+            .filter(|warning| self.stop_timeout.is_none_or(|timeout| *warning < timeout));
+        if let Some(warning) = warning
+            && let Err(RecvTimeoutError::Timeout) = done_receiver.recv_timeout(warning)
+        {
+            tracing::warn!(
+                "Service '{}' has not stopped after {warning:?}. Still waiting...",
+                self.name
+            );
         }
-        Ok(())
+
+        if let Some(timeout) = self.stop_timeout {
+            let remaining = timeout.saturating_sub(started.elapsed());
+            if let Err(RecvTimeoutError::Timeout) = done_receiver.recv_timeout(remaining) {
+                tracing::error!(
+                    "Service '{}' did not stop within {timeout:?}. Exiting with code {STOP_TIMEOUT_EXIT_CODE}.",
+                    self.name
+                );
+                process::exit(STOP_TIMEOUT_EXIT_CODE);
+            }
+        }
+    }
+
+    // Returns the error returned by the service function (if any), so it can become the exit code
+    fn join_thread(&self, handle: JoinHandle<Result<()>>) -> Result<()> {
+        handle.join().map_err(|_| "Error joining thread")?
     }
 }
 
 impl<F> BaseService<F, Receiver<()>>
 where
-    F: FnOnce(Receiver<()>, bool) -> Result<()>,
+    F: FnOnce(Receiver<()>, ServiceContext<Receiver<()>>) -> Result<()>,
 {
     /// Creates a new base service for synchronous applications. `service_fn` is the function that will
     /// be executed by the service. A synchronous receiver channel will be passed to the service function
-    /// and will receive a message when the service should shutdown.
+    /// and will receive a message when the service should shutdown. A second channel for reload notifications
+    /// is available via [`ServiceContext::take_reload_receiver`].
     pub fn new_sync(name: impl Into<String>, service_fn: F, is_service: bool) -> Self {
-        let (sender, receiver) = channel();
-        let sender = move || {
-            sender.send(())?;
-            Ok(())
-        };
+        let (sender, receiver) = sync_channel();
+        let (reload_sender, reload_receiver) = sync_channel();
         Self::new(name, service_fn, is_service, sender, receiver)
+            .with_reload(reload_sender, reload_receiver)
+    }
+
+    /// Sets up pause and resume notifications with synchronous channels, so the service can be paused.
+    /// See [`BaseService::with_pause`](BaseService#method.with_pause).
+    pub fn with_pause_channels(self) -> Self {
+        let (pause_sender, pause_receiver) = sync_channel();
+        let (resume_sender, resume_receiver) = sync_channel();
+        self.with_pause(pause_sender, pause_receiver, resume_sender, resume_receiver)
+    }
+
+    /// Restarts the service function in-process after it returns an error or panics, as directed by
+    /// `policy`. Each run gets new shutdown, reload and pause receivers (and starts unpaused). Sockets passed in by the service manager
+    /// are only available to the first run, as the previous run's sockets are closed along with it.
+    pub fn with_restart_policy(
+        self,
+        policy: RestartPolicy,
+    ) -> BaseService<RestartingFn<Receiver<()>>, Receiver<()>>
+    where
+        F: FnMut(Receiver<()>, ServiceContext<Receiver<()>>) -> Result<()> + Send + 'static,
+    {
+        self.into_restarting(policy, sync_channel)
     }
 }
 
+fn sync_channel() -> (SenderFn, Receiver<()>) {
+    let (sender, receiver) = channel();
+    let sender = move || {
+        // If the receiver is gone, the service function isn't listening (it might be relying on the
+        // shutdown token instead)
+        let _ = sender.send(());
+        Ok(())
+    };
+    (Box::new(sender), receiver)
+}
+
 #[cfg(feature = "tokio")]
 impl<F> BaseService<F, tokio::sync::mpsc::Receiver<()>>
 where
-    F: FnOnce(tokio::sync::mpsc::Receiver<()>, bool) -> Result<()>,
+    F: FnOnce(
+        tokio::sync::mpsc::Receiver<()>,
+        ServiceContext<tokio::sync::mpsc::Receiver<()>>,
+    ) -> Result<()>,
 {
     /// Creates a new base service for `tokio` asynchronous applications. `service_fn` is the function that will
     /// be executed by the service. An asynchronous receiver channel will be passed to the service function
-    /// and will receive a message when the service should shutdown.
+    /// and will receive a message when the service should shutdown. A second channel for reload notifications
+    /// is available via [`ServiceContext::take_reload_receiver`].
     pub fn new_tokio(name: impl Into<String>, service_fn: F, is_service: bool) -> Self {
-        let (sender, receiver) = tokio::sync::mpsc::channel(1);
-        let sender = move || {
-            sender.blocking_send(())?;
-            Ok(())
-        };
+        let (sender, receiver) = tokio_channel();
+        let (reload_sender, reload_receiver) = tokio_channel();
         Self::new(name, service_fn, is_service, sender, receiver)
+            .with_reload(reload_sender, reload_receiver)
+    }
+
+    /// Sets up pause and resume notifications with asynchronous channels, so the service can be paused.
+    /// See [`BaseService::with_pause`](BaseService#method.with_pause).
+    pub fn with_pause_channels(self) -> Self {
+        let (pause_sender, pause_receiver) = tokio_channel();
+        let (resume_sender, resume_receiver) = tokio_channel();
+        self.with_pause(pause_sender, pause_receiver, resume_sender, resume_receiver)
+    }
+
+    /// Restarts the service function in-process after it returns an error or panics.
+    /// See [`BaseService::with_restart_policy`](BaseService#method.with_restart_policy).
+    pub fn with_restart_policy(
+        self,
+        policy: RestartPolicy,
+    ) -> BaseService<RestartingFn<tokio::sync::mpsc::Receiver<()>>, tokio::sync::mpsc::Receiver<()>>
+    where
+        F: FnMut(
+                tokio::sync::mpsc::Receiver<()>,
+                ServiceContext<tokio::sync::mpsc::Receiver<()>>,
+            ) -> Result<()>
+            + Send
+            + 'static,
+    {
+        self.into_restarting(policy, tokio_channel)
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn tokio_channel() -> (SenderFn, tokio::sync::mpsc::Receiver<()>) {
+    let (sender, receiver) = tokio::sync::mpsc::channel(1);
+    // `try_send` (unlike `blocking_send`) is safe to call from within a runtime
+    let sender = move || {
+        // If the channel is full, a notification is already pending. If the receiver is gone, the
+        // service function isn't listening (it might be relying on the shutdown token instead).
+        let _ = sender.try_send(());
+        Ok(())
+    };
+    (Box::new(sender), receiver)
+}
+
+// *** Restarts ***
+
+impl<F, R> BaseService<F, R>
+where
+    F: FnMut(R, ServiceContext<R>) -> Result<()> + Send + 'static,
+    R: Send + 'static,
+{
+    // Wraps the service function in one that re-runs it as directed by the policy. The senders are shared
+    // with the wrapper so it can swap in the senders for the new receivers before each restart.
+    fn into_restarting(
+        self,
+        policy: RestartPolicy,
+        channel_fn: impl Fn() -> (SenderFn, R) + Send + 'static,
+    ) -> BaseService<RestartingFn<R>, R> {
+        let name = self.name.clone();
+        let crash_report_dir = self.crash_report_dir.clone();
+        let mut service_fn = self.service_fn.expect("Service function not found");
+        let sender = Arc::new(Mutex::new(self.sender_fn));
+        let has_reload = self.reload_sender_fn.is_some();
+        let reload_sender = Arc::new(Mutex::new(self.reload_sender_fn));
+        let has_pause = self.pause_sender_fns.is_some();
+        let pause_senders = Arc::new(Mutex::new(self.pause_sender_fns));
+
+        let restarting_fn = {
+            let (sender, reload_sender) = (sender.clone(), reload_sender.clone());
+            let pause_senders = pause_senders.clone();
+
+            move |mut receiver: R, mut context: ServiceContext<R>| -> Result<()> {
+                let is_service = context.is_service();
+                let shutdown_token = context.shutdown_token().clone();
+                let mut failures = 0;
+
+                loop {
+                    let started = Instant::now();
+                    // Panics are caught per run, so a panicking run is restarted like a failed one
+                    let err = match catch_panic(&name, crash_report_dir.as_deref(), || {
+                        service_fn(receiver, context)
+                    }) {
+                        Ok(()) => return Ok(()),
+                        // Failing while shutting down is not a reason to restart
+                        Err(err) if shutdown_token.is_cancelled() => return Err(err),
+                        Err(err) => err,
+                    };
+
+                    if started.elapsed() >= policy.reset_window() {
+                        failures = 0;
+                    }
+                    failures += 1;
+                    let Some(backoff) = policy.backoff(failures) else {
+                        tracing::error!(
+                            "Service '{name}' failed {failures} times in a row. Giving up: {err}"
+                        );
+                        return Err(err);
+                    };
+
+                    tracing::warn!(
+                        "Service '{name}' failed: {err}. Restarting in {backoff:?} (restart {failures})..."
+                    );
+                    if shutdown_token.wait_timeout(backoff) {
+                        return Ok(());
+                    }
+
+                    let (new_sender, new_receiver) = channel_fn();
+                    *sender.lock().expect("Mutex poisoned") = new_sender;
+                    let reload_receiver = has_reload.then(|| {
+                        let (new_sender, new_receiver) = channel_fn();
+                        *reload_sender.lock().expect("Mutex poisoned") = Some(new_sender);
+                        new_receiver
+                    });
+                    let pause_receivers = has_pause.then(|| {
+                        let (pause_sender, pause_receiver) = channel_fn();
+                        let (resume_sender, resume_receiver) = channel_fn();
+                        *pause_senders.lock().expect("Mutex poisoned") =
+                            Some((pause_sender, resume_sender));
+                        (pause_receiver, resume_receiver)
+                    });
+
+                    // Checked after swapping the senders, so a shutdown is either seen here or sent
+                    // to the new receiver
+                    if shutdown_token.is_cancelled() {
+                        return Ok(());
+                    }
+                    tracing::info!("Restarting service '{name}'...");
+                    receiver = new_receiver;
+                    context = ServiceContext::new(
+                        is_service,
+                        ListenFds::default(),
+                        reload_receiver,
+                        pause_receivers,
+                        shutdown_token.clone(),
+                        None,
+                    );
+                }
+            }
+        };
+
+        BaseService {
+            name: self.name,
+            service_fn: Some(Box::new(restarting_fn)),
+            sender_fn: Box::new(move || (sender.lock().expect("Mutex poisoned"))()),
+            receiver: self.receiver,
+            handle: self.handle,
+            is_service: self.is_service,
+            health_fn: self.health_fn,
+            reload_sender_fn: has_reload.then(|| {
+                Box::new(
+                    move || match &*reload_sender.lock().expect("Mutex poisoned") {
+                        Some(sender_fn) => sender_fn(),
+                        None => Ok(()),
+                    },
+                ) as SenderFn
+            }),
+            reload_receiver: self.reload_receiver,
+            pause_sender_fns: has_pause.then(|| {
+                let resume_senders = pause_senders.clone();
+                (
+                    Box::new(
+                        move || match &*pause_senders.lock().expect("Mutex poisoned") {
+                            Some((sender_fn, _)) => sender_fn(),
+                            None => Ok(()),
+                        },
+                    ) as SenderFn,
+                    Box::new(
+                        move || match &*resume_senders.lock().expect("Mutex poisoned") {
+                            Some((_, sender_fn)) => sender_fn(),
+                            None => Ok(()),
+                        },
+                    ) as SenderFn,
+                )
+            }),
+            pause_receivers: self.pause_receivers,
+            command_fn: self.command_fn,
+            shutdown_token: self.shutdown_token,
+            done_receiver: self.done_receiver,
+            exit_notifier: self.exit_notifier,
+            start_hook: self.start_hook,
+            stop_warning: self.stop_warning,
+            stop_timeout: self.stop_timeout,
+            crash_report_dir: self.crash_report_dir,
+        }
     }
 }
 
 impl<F, R> ServiceApp for BaseService<F, R>
 where
-    F: FnOnce(R, bool) -> Result<()> + Send + 'static,
+    F: FnOnce(R, ServiceContext<R>) -> Result<()> + Send + 'static,
     R: Send + 'static,
 {
     fn name(&self) -> &str {
@@ -98,14 +479,44 @@ where
 
         let receiver = mem::take(&mut self.receiver)
             .ok_or("Receiver not found (service might have been started twice)")?;
-        let is_service = self.is_service;
+        let context = ServiceContext::new(
+            self.is_service,
+            ListenFds::from_env()?,
+            mem::take(&mut self.reload_receiver),
+            mem::take(&mut self.pause_receivers),
+            self.shutdown_token.clone(),
+            self.start_hook.take(),
+        );
         let service_fn = mem::take(&mut self.service_fn).ok_or("Service function not found")?;
 
-        self.handle = Some(thread::spawn(move || service_fn(receiver, is_service)));
+        let (done_sender, done_receiver) = channel::<()>();
+        self.done_receiver = Some(done_receiver);
+
+        let exit_guard = ExitGuard {
+            _done_sender: done_sender,
+            exit_notifier: self.exit_notifier.clone(),
+        };
+
+        let name = self.name.clone();
+        let crash_report_dir = self.crash_report_dir.clone();
+
+        self.handle = Some(thread::spawn(move || {
+            // Dropped when the service function returns or panics
+            let _exit_guard = exit_guard;
+            catch_panic(&name, crash_report_dir.as_deref(), || {
+                service_fn(receiver, context)
+            })
+        }));
         Ok(())
     }
 
-    fn stop(mut self: Box<Self>) -> Result<()> {
+    fn stop(self: Box<Self>) -> Result<()> {
+        self.stop_with_reason(ShutdownReason::Programmatic)
+    }
+
+    fn stop_with_reason(mut self: Box<Self>, reason: ShutdownReason) -> Result<()> {
+        self.shutdown_token.cancel_with_reason(reason);
+
         match mem::take(&mut self.handle) {
             Some(handle) if handle.is_finished() => {
                 tracing::warn!(
@@ -116,9 +527,10 @@ where
                 Ok(())
             }
             Some(handle) => {
-                tracing::info!("Stopping service '{}'...", self.name);
+                tracing::info!("Stopping service '{}' ({reason})...", self.name);
                 (self.sender_fn)()?;
 
+                self.wait_for_thread();
                 self.join_thread(handle)?;
 
                 tracing::info!("Service '{}' is shut down.", self.name);
@@ -128,10 +540,90 @@ where
         }
     }
 
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.exit_notifier = Some(notifier);
+        true
+    }
+
+    // Run by the service function through `ServiceContext::run_start_hook`
+    fn set_start_hook(&mut self, hook: StartHook) -> bool {
+        self.start_hook = Some(hook);
+        true
+    }
+
     fn is_running(&self) -> bool {
         self.handle
             .as_ref()
             .map(|handle| !handle.is_finished())
             .unwrap_or(false)
     }
+
+    fn is_healthy(&self) -> bool {
+        self.health().state() != HealthState::Failing
+    }
+
+    fn health(&self) -> HealthReport {
+        self.health_fn
+            .as_ref()
+            .map_or_else(HealthReport::ok, |health_fn| health_fn())
+    }
+
+    fn stop_timeout(&self) -> Option<Duration> {
+        self.stop_timeout
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        match &self.reload_sender_fn {
+            Some(sender_fn) => sender_fn(),
+            None => {
+                tracing::warn!("Service '{}' does not support reloading", self.name);
+                Ok(())
+            }
+        }
+    }
+
+    fn can_pause(&self) -> bool {
+        self.pause_sender_fns.is_some()
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        match &self.pause_sender_fns {
+            Some((sender_fn, _)) => sender_fn(),
+            None => Ok(()),
+        }
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        match &self.pause_sender_fns {
+            Some((_, sender_fn)) => sender_fn(),
+            None => Ok(()),
+        }
+    }
+
+    fn command(&mut self, command: u32) -> Result<()> {
+        match &mut self.command_fn {
+            Some(command_fn) => command_fn(command),
+            None => {
+                tracing::warn!("Service '{}' does not handle command {command}", self.name);
+                Ok(())
+            }
+        }
+    }
+}
+
+// *** ExitGuard ***
+
+/// Signals that the service thread exited when dropped (including when the service function panics)
+struct ExitGuard {
+    // Dropping the sender disconnects the channel
+    _done_sender: Sender<()>,
+    exit_notifier: Option<ExitNotifier>,
+}
+
+impl Drop for ExitGuard {
+    fn drop(&mut self) {
+        if let Some(exit_notifier) = &self.exit_notifier {
+            exit_notifier.notify();
+        }
+    }
 }
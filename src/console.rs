@@ -0,0 +1,109 @@
+use std::{
+    io::{self, BufRead as _},
+    sync::{Mutex, MutexGuard, Once},
+    thread,
+};
+
+use crate::{ControlEvent, ControlSender, ShutdownReason};
+
+const HELP: &str = "Commands:
+  stop     Stop the service
+  reload   Reload the service configuration
+  pause    Pause the service
+  resume   Resume the paused service
+  command  Send a command to the service (for example, 'command 1')
+  status   Show the service status
+  help     Show this help";
+
+static START_READER: Once = Once::new();
+// Stdin can only be read by one thread, so a single reader sends the commands to the current run (if any)
+static CONSOLE: Mutex<Option<(u64, ControlSender)>> = Mutex::new(None);
+
+fn console() -> MutexGuard<'static, Option<(u64, ControlSender)>> {
+    CONSOLE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Detaches the console from the run when dropped
+#[must_use = "The console is detached when dropped"]
+pub(crate) struct ConsoleSubscription(u64);
+
+impl Drop for ConsoleSubscription {
+    fn drop(&mut self) {
+        let mut console = console();
+        if console.as_ref().is_some_and(|(id, _)| *id == self.0) {
+            *console = None;
+        }
+    }
+}
+
+/// Attaches the console to a run, so the commands read from stdin are sent to `tx` as control events. If
+/// several runs are attached, the commands go to the last one.
+pub(crate) fn attach(tx: ControlSender) -> ConsoleSubscription {
+    static NEXT_ID: Mutex<u64> = Mutex::new(0);
+    let id = {
+        let mut next_id = NEXT_ID.lock().unwrap_or_else(|err| err.into_inner());
+        *next_id += 1;
+        *next_id
+    };
+    *console() = Some((id, tx));
+
+    START_READER.call_once(|| {
+        thread::spawn(read_commands);
+    });
+    println!("Service console ready. Type 'help' for the list of commands");
+    ConsoleSubscription(id)
+}
+
+fn read_commands() {
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::error!("Could not read console command: {err}");
+                break;
+            }
+        };
+
+        let event = match line.trim() {
+            "" => continue,
+            "stop" | "quit" | "exit" => ControlEvent::Shutdown(ShutdownReason::Programmatic),
+            "reload" => ControlEvent::Reload,
+            "pause" => ControlEvent::Pause,
+            "resume" => ControlEvent::Resume,
+            "status" => ControlEvent::Status,
+            line if line.split_whitespace().next() == Some("command") => {
+                match parse_command(line) {
+                    Some(command) => ControlEvent::Command(command),
+                    None => {
+                        println!("Usage: command <number>");
+                        continue;
+                    }
+                }
+            }
+            "help" | "?" => {
+                println!("{HELP}");
+                continue;
+            }
+            command => {
+                println!("Unknown command '{command}'. Type 'help' for the list of commands");
+                continue;
+            }
+        };
+
+        match &*console() {
+            // If the receiver is gone, the run is about to detach
+            Some((_, tx)) => {
+                tx.send(event);
+            }
+            None => println!("No service is running"),
+        }
+    }
+}
+
+// Parses the number in "command <number>"
+pub(crate) fn parse_command(line: &str) -> Option<u32> {
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        [_, command] => command.parse().ok(),
+        _ => None,
+    }
+}
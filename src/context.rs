@@ -0,0 +1,95 @@
+use crate::{ListenFds, Result, ShutdownReason, ShutdownToken, StartHook};
+
+/// The context passed to a [`BaseService`](crate::BaseService) service function. `R` is the type of
+/// notification receiver used by the service (the same type as the shutdown receiver).
+pub struct ServiceContext<R> {
+    is_service: bool,
+    listen_fds: ListenFds,
+    reload_receiver: Option<R>,
+    pause_receiver: Option<R>,
+    resume_receiver: Option<R>,
+    shutdown_token: ShutdownToken,
+    start_hook: Option<StartHook>,
+}
+
+impl<R> ServiceContext<R> {
+    pub(crate) fn new(
+        is_service: bool,
+        listen_fds: ListenFds,
+        reload_receiver: Option<R>,
+        pause_receivers: Option<(R, R)>,
+        shutdown_token: ShutdownToken,
+        start_hook: Option<StartHook>,
+    ) -> Self {
+        let (pause_receiver, resume_receiver) = pause_receivers.unzip();
+        Self {
+            is_service,
+            listen_fds,
+            reload_receiver,
+            pause_receiver,
+            resume_receiver,
+            shutdown_token,
+            start_hook,
+        }
+    }
+
+    /// Returns `true` if the service was started by the service manager, `false` if it was started
+    /// interactively.
+    pub fn is_service(&self) -> bool {
+        self.is_service
+    }
+
+    /// Returns the sockets passed to the process by the service manager (socket activation). The sockets
+    /// can be taken by name and used in place of binding new ones. Only the first service started in the
+    /// process receives them.
+    pub fn listen_fds(&mut self) -> &mut ListenFds {
+        &mut self.listen_fds
+    }
+
+    /// Takes the receiver of reload notifications. It receives a message each time the service is asked
+    /// to reload its configuration (`SIGHUP` on Unix). Returns `None` if the service was not set up for
+    /// reloading or the receiver was already taken.
+    pub fn take_reload_receiver(&mut self) -> Option<R> {
+        self.reload_receiver.take()
+    }
+
+    /// Takes the receiver of pause notifications. It receives a message each time the service is asked to
+    /// pause. Returns `None` if the service was not set up for pausing or the receiver was already taken.
+    pub fn take_pause_receiver(&mut self) -> Option<R> {
+        self.pause_receiver.take()
+    }
+
+    /// Takes the receiver of resume notifications. It receives a message each time the paused service is
+    /// asked to resume. Returns `None` if the service was not set up for pausing or the receiver was already
+    /// taken.
+    pub fn take_resume_receiver(&mut self) -> Option<R> {
+        self.resume_receiver.take()
+    }
+
+    /// Returns the shutdown token. It is cancelled when the service should shutdown, at the same time the
+    /// shutdown receiver receives its message. Unlike the receiver, it can be cloned and handed to any number
+    /// of threads and tasks, and child tokens can be created for subsystems.
+    pub fn shutdown_token(&self) -> &ShutdownToken {
+        &self.shutdown_token
+    }
+
+    /// Returns why the service is being shut down, or `None` if it hasn't been asked to shut down yet.
+    /// This is set before the shutdown receiver gets its message.
+    pub fn shutdown_reason(&self) -> Option<ShutdownReason> {
+        self.shutdown_token.reason()
+    }
+
+    /// Runs the [start hook](crate::ServiceApp::set_start_hook) set on the service, if any. The service
+    /// function should call it once it has acquired the resources it needs privileges for, such as binding
+    /// privileged ports, so a wrapping [`DropPrivileges`](crate::DropPrivileges) can drop them. Its error
+    /// should be returned by the service function. Calling it again does nothing.
+    ///
+    /// If the service has a start hook, starting the service waits until this is called (or the service
+    /// function returns, which fails the start).
+    pub fn run_start_hook(&mut self) -> Result<()> {
+        match self.start_hook.take() {
+            Some(start_hook) => start_hook(),
+            None => Ok(()),
+        }
+    }
+}
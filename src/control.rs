@@ -0,0 +1,212 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, BufRead as _, BufReader, Write as _},
+    os::unix::{
+        fs::{DirBuilderExt as _, FileTypeExt as _, PermissionsExt as _},
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::channel,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    ControlEvent, ControlSender, HealthReport, Result, ShutdownReason, console::parse_command,
+};
+
+// How long a client waits for the runtime to answer a query (it might be busy reloading, for example)
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state of the service reported by the runtime to the control socket
+pub(crate) struct ControlReport {
+    pub(crate) paused: bool,
+    pub(crate) health: HealthReport,
+}
+
+/// Returns the path of the control socket of the service named `name` (see
+/// [`RunOptions::with_control_socket`](crate::RunOptions::with_control_socket)). It is in `/run` (or `/var/run`)
+/// when running as root, and otherwise in `$XDG_RUNTIME_DIR` (or the temporary directory if not set). Path
+/// separators and a leading `.` in `name` are replaced with `_`, so the socket is always in that directory.
+pub fn control_socket_path(name: &str) -> PathBuf {
+    // SAFETY: `geteuid` is always successful and has no preconditions
+    let dir = if unsafe { libc::geteuid() } == 0 {
+        match Path::new("/run").is_dir() {
+            true => PathBuf::from("/run"),
+            false => PathBuf::from("/var/run"),
+        }
+    } else {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+    };
+    dir.join(format!("{}.sock", socket_file_stem(name)))
+}
+
+// `uni_service_manager` escapes the name the same way to find the socket
+fn socket_file_stem(name: &str) -> String {
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| match c {
+            '/' | '\\' | '\0' => '_',
+            '.' if i == 0 => '_',
+            c => c,
+        })
+        .collect()
+}
+
+// The socket can stop the service, so only its owner can use it. It is bound in a private directory and
+// only linked at `path` once its permissions are set, so it is never reachable with the permissions it was
+// created with (as happens when binding at `path` and then changing them)
+fn bind_private(path: &Path) -> Result<UnixListener> {
+    static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid control socket path '{}'", path.display()))?;
+    let mut dir_name = OsString::from(format!(".{}-", process::id()));
+    dir_name.push(NEXT_ID.fetch_add(1, Ordering::Relaxed).to_string());
+    let dir = path.with_file_name(dir_name);
+    let private_path = dir.join(name);
+
+    fs::DirBuilder::new().mode(0o700).create(&dir)?;
+    let result = UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+        // Unlike a rename, this fails if another process bound `path` in the meantime
+        fs::hard_link(&private_path, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&private_path);
+    if let Err(err) = fs::remove_dir(&dir) {
+        tracing::warn!("Could not remove '{}': {err}", dir.display());
+    }
+    Ok(result?)
+}
+
+/// Stops listening and removes the socket when dropped
+#[must_use = "The control socket is closed when dropped"]
+pub(crate) struct ControlSocket {
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+        // Wakes up the listener so it sees it was stopped
+        let _ = UnixStream::connect(&self.path);
+        if let Err(err) = fs::remove_file(&self.path) {
+            tracing::warn!("Could not remove '{}': {err}", self.path.display());
+        }
+    }
+}
+
+/// Listens on the control socket at `path`, sending the requests to the runtime over `tx` as control events.
+/// Each client is served on its own thread.
+pub(crate) fn listen(path: &Path, tx: ControlSender) -> Result<ControlSocket> {
+    // A socket left behind by a process that crashed is removed, but not one still in use, nor anything
+    // that isn't a socket
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.file_type().is_socket() => {
+            return Err(format!("'{}' exists and is not a socket", path.display()).into());
+        }
+        Ok(_) if UnixStream::connect(path).is_ok() => {
+            return Err(format!("Control socket '{}' is already in use", path.display()).into());
+        }
+        Ok(_) => fs::remove_file(path)?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let listener = bind_private(path)?;
+    tracing::debug!("Listening on control socket '{}'", path.display());
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    {
+        let stopped = stopped.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if stopped.load(Ordering::Acquire) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let tx = tx.clone();
+                        thread::spawn(move || {
+                            if let Err(err) = serve(stream, &tx) {
+                                tracing::debug!("Control socket client failed: {err}");
+                            }
+                        });
+                    }
+                    Err(err) => tracing::warn!("Could not accept control socket client: {err}"),
+                }
+            }
+        });
+    }
+
+    Ok(ControlSocket {
+        path: path.to_path_buf(),
+        stopped,
+    })
+}
+
+// Answers each request line with a response line until the client disconnects
+fn serve(stream: UnixStream, tx: &ControlSender) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let response = match handle_request(line?.trim(), tx) {
+            Ok(Some(value)) => format!("ok {value}"),
+            Ok(None) => "ok".to_string(),
+            Err(err) => format!("error {err}"),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+// Returns the value of the response, if any
+fn handle_request(request: &str, tx: &ControlSender) -> Result<Option<String>> {
+    let event = match request {
+        "status" | "health" => {
+            let report = query(tx)?;
+            return Ok(Some(match request {
+                "status" if report.paused => "paused".to_string(),
+                "status" => "running".to_string(),
+                // The report is a single line, unless a message spans several
+                _ => report.health.to_string().replace(['\r', '\n'], " "),
+            }));
+        }
+        "stop" => ControlEvent::Shutdown(ShutdownReason::Programmatic),
+        "reload" => ControlEvent::Reload,
+        "pause" => ControlEvent::Pause,
+        "resume" => ControlEvent::Resume,
+        request if request.split_whitespace().next() == Some("command") => {
+            match parse_command(request) {
+                Some(command) => ControlEvent::Command(command),
+                None => return Err("Usage: command <number>".into()),
+            }
+        }
+        request => return Err(format!("Unknown request '{request}'").into()),
+    };
+
+    if !tx.send(event) {
+        return Err("Service is not running".into());
+    }
+    Ok(None)
+}
+
+fn query(tx: &ControlSender) -> Result<ControlReport> {
+    let (reply_tx, reply_rx) = channel();
+    if !tx.send(ControlEvent::Query(reply_tx)) {
+        return Err("Service is not running".into());
+    }
+    Ok(reply_rx
+        .recv_timeout(REPLY_TIMEOUT)
+        .map_err(|_| "Service did not answer")?)
+}
@@ -0,0 +1,174 @@
+use std::{
+    ffi::c_int,
+    fs::{File, OpenOptions},
+    io::{self, PipeWriter, Read as _, Write as _},
+    os::fd::AsRawFd,
+    path::{self, Path, PathBuf},
+    process,
+    sync::mpsc::channel,
+};
+
+use crate::{
+    ControlSender, FAILURE_EXIT_CODE, Result, RunOptions, ServiceApp, ServiceState,
+    pid_file::PidFile, run_app, signals,
+};
+
+/// Options for running a service as a classic Unix daemon. See [`run_service_daemon`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DaemonOptions {
+    pid_file: Option<PathBuf>,
+    stdout: Option<PathBuf>,
+    stderr: Option<PathBuf>,
+    umask: u32,
+    working_dir: PathBuf,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        Self {
+            pid_file: None,
+            stdout: None,
+            stderr: None,
+            umask: 0,
+            working_dir: PathBuf::from("/"),
+        }
+    }
+}
+
+impl DaemonOptions {
+    /// Creates daemon options with the default settings: no PID file, stdout and stderr redirected to
+    /// `/dev/null`, a umask of 0 and `/` as the working directory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes the PID of the daemon to `path`, which is locked while the daemon runs (so a second instance
+    /// fails to start) and removed once it stops.
+    pub fn with_pid_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.pid_file = Some(path.into());
+        self
+    }
+
+    /// Appends stdout to the file at `path` (created if needed) instead of discarding it.
+    pub fn with_stdout(mut self, path: impl Into<PathBuf>) -> Self {
+        self.stdout = Some(path.into());
+        self
+    }
+
+    /// Appends stderr to the file at `path` (created if needed) instead of discarding it.
+    pub fn with_stderr(mut self, path: impl Into<PathBuf>) -> Self {
+        self.stderr = Some(path.into());
+        self
+    }
+
+    /// Sets the umask of the daemon.
+    pub fn with_umask(mut self, umask: u32) -> Self {
+        self.umask = umask;
+        self
+    }
+
+    /// Sets the working directory of the daemon.
+    pub fn with_working_dir(mut self, working_dir: impl Into<PathBuf>) -> Self {
+        self.working_dir = working_dir.into();
+        self
+    }
+}
+
+/// Executes a service as a classic Unix daemon, for init systems that don't supervise their services (SysV
+/// init scripts, cron `@reboot`, shells...). The process detaches from its terminal (double fork and
+/// `setsid`), resets its umask, changes its working directory and redirects its stdio as set in `options`,
+/// then runs the service just like [`run_service`](crate::run_service).
+///
+/// The original process waits until the service has started, then exits with code 0, or with
+/// [`FAILURE_EXIT_CODE`] if the daemon failed before that. Relative paths in `options` are relative to the
+/// original working directory.
+///
+/// This must be called before any other threads are spawned, as only the calling thread survives a fork.
+pub fn run_service_daemon(
+    app: impl ServiceApp + Send + 'static,
+    options: DaemonOptions,
+) -> Result<()> {
+    let name = app.name().to_string();
+    let pid_file = options
+        .pid_file
+        .as_deref()
+        .map(path::absolute)
+        .transpose()?;
+    let ready = daemonize(&name, &options)?;
+    let _pid_file = pid_file.as_deref().map(PidFile::create).transpose()?;
+
+    let (control_tx, control_rx) = channel();
+    let options = RunOptions::default();
+    let _signals = signals::install_handler(ControlSender::Blocking(control_tx.clone()), &options)?;
+    run_app(Box::new(app), control_tx, control_rx, &options, |state| {
+        if state == ServiceState::Running {
+            // If the original process is gone, nobody is waiting
+            let _ = (&ready).write_all(&[1]);
+        }
+    })
+}
+
+// Only returns in the daemon, with the pipe used to tell the original process the service started
+fn daemonize(name: &str, options: &DaemonOptions) -> Result<PipeWriter> {
+    // Opened before forking, so errors are returned to the caller
+    let stdin = File::open("/dev/null")?;
+    let stdout = open_output(options.stdout.as_deref())?;
+    let stderr = open_output(options.stderr.as_deref())?;
+    let (mut reader, writer) = io::pipe()?;
+
+    if fork()? != 0 {
+        drop(writer);
+        // The pipe is closed without a write if the daemon fails before the service starts
+        let mut started = [0; 1];
+        let code = match reader.read(&mut started) {
+            Ok(1) => 0,
+            _ => {
+                tracing::error!("Daemon '{name}' failed to start");
+                FAILURE_EXIT_CODE
+            }
+        };
+        process::exit(code);
+    }
+    drop(reader);
+
+    // SAFETY: `setsid` has no preconditions
+    if unsafe { libc::setsid() } == -1 {
+        return Err(io::Error::last_os_error().into());
+    }
+    // The session leader exits, so the daemon can never acquire a controlling terminal again
+    if fork()? != 0 {
+        process::exit(0);
+    }
+
+    // SAFETY: `umask` is always successful and has no preconditions
+    unsafe { libc::umask(options.umask as libc::mode_t) };
+    std::env::set_current_dir(&options.working_dir)?;
+
+    redirect(&stdin, libc::STDIN_FILENO)?;
+    redirect(&stdout, libc::STDOUT_FILENO)?;
+    redirect(&stderr, libc::STDERR_FILENO)?;
+    Ok(writer)
+}
+
+fn fork() -> io::Result<libc::pid_t> {
+    // SAFETY: The caller must not have spawned other threads (see `run_service_daemon`)
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error()),
+        pid => Ok(pid),
+    }
+}
+
+fn open_output(path: Option<&Path>) -> io::Result<File> {
+    match path {
+        Some(path) => OpenOptions::new().create(true).append(true).open(path),
+        None => OpenOptions::new().write(true).open("/dev/null"),
+    }
+}
+
+fn redirect(file: &File, fd: c_int) -> io::Result<()> {
+    // SAFETY: Both file descriptors are valid for the duration of the call
+    match unsafe { libc::dup2(file.as_raw_fd(), fd) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,103 @@
+use std::{error::Error, fmt, sync::Arc};
+
+use crate::{PANIC_EXIT_CODE, PanicError};
+
+// *** ExitNotifier ***
+
+/// Notifies the runtime that the service app stopped running on its own, so it can react immediately
+/// instead of polling [`ServiceApp::is_running`](crate::ServiceApp::is_running).
+/// See [`ServiceApp::set_exit_notifier`](crate::ServiceApp::set_exit_notifier).
+#[derive(Clone)]
+pub struct ExitNotifier {
+    notify_fn: Arc<dyn Fn() + Send + Sync>,
+}
+
+impl ExitNotifier {
+    pub(crate) fn new(notify_fn: impl Fn() + Send + Sync + 'static) -> Self {
+        Self {
+            notify_fn: Arc::new(notify_fn),
+        }
+    }
+
+    /// Notifies the runtime that the app is no longer running. Calling this more than once, or after the
+    /// runtime has stopped waiting, does no harm.
+    pub fn notify(&self) {
+        (self.notify_fn)();
+    }
+}
+
+impl fmt::Debug for ExitNotifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExitNotifier").finish_non_exhaustive()
+    }
+}
+
+// *** ExitCodeError ***
+
+/// The exit code used for errors that don't specify one. See [`exit_code`].
+pub const FAILURE_EXIT_CODE: i32 = 1;
+
+/// An error that carries the exit code the process should exit with. Return it (typically wrapping the
+/// underlying error) from a service function or [`ServiceApp`](crate::ServiceApp) method, and use
+/// [`exit_code`] on the error returned by [`run_service`](crate::run_service) to exit the process with it.
+#[derive(Debug)]
+pub struct ExitCodeError {
+    code: i32,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl ExitCodeError {
+    /// Creates a new error with the given exit code.
+    pub fn new(code: i32) -> Self {
+        Self { code, source: None }
+    }
+
+    /// Creates a new error with the given exit code, wrapping `source`.
+    pub fn with_source(code: i32, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            code,
+            source: Some(source.into()),
+        }
+    }
+
+    /// Returns the exit code.
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+}
+
+impl fmt::Display for ExitCodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{source} (exit code {})", self.code),
+            None => write!(f, "Exited with code {}", self.code),
+        }
+    }
+}
+
+impl Error for ExitCodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+/// Returns the process exit code for an error returned by [`run_service`](crate::run_service). This is the
+/// code of the first [`ExitCodeError`] found in the error or its chain of sources, [`PANIC_EXIT_CODE`] for
+/// a [`PanicError`], else [`FAILURE_EXIT_CODE`].
+/// Exiting non-zero lets the service manager react to the failure (for example, systemd's
+/// `Restart=on-failure`).
+pub fn exit_code(err: &(dyn Error + 'static)) -> i32 {
+    let mut err = Some(err);
+    while let Some(current) = err {
+        if let Some(exit_err) = current.downcast_ref::<ExitCodeError>() {
+            return exit_err.code;
+        }
+        if current.is::<PanicError>() {
+            return PANIC_EXIT_CODE;
+        }
+        err = current.source();
+    }
+    FAILURE_EXIT_CODE
+}
@@ -0,0 +1,785 @@
+use std::{
+    collections::HashSet,
+    mem,
+    sync::{
+        Arc, Condvar, Mutex, MutexGuard,
+        mpsc::{Receiver, RecvTimeoutError, Sender, channel},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ExitNotifier, HealthReport, HealthState, POLL_INTERVAL, RestartPolicy, Result, ServiceApp,
+    ShutdownReason, StartHook,
+};
+
+/// Creates a new instance of a child service, so it can be restarted. See [`ChildService::from_factory`].
+pub type ServiceFactory = Box<dyn FnMut() -> Box<dyn ServiceApp + Send> + Send>;
+
+/// What a [`ServiceGroup`] does when one of its children stops running on its own.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ChildExitPolicy {
+    /// Stop the whole group. The child's error, if any, is returned when the group is stopped. This is the default.
+    #[default]
+    StopAll,
+    /// Replace the child with a new instance from its factory and start it after the policy's backoff, if it
+    /// failed (returned an error or panicked). A child that stopped cleanly is left stopped, as with
+    /// [`Ignore`](Self::Ignore). Once the retries are exhausted, the whole group is stopped. Requires
+    /// [`ChildService::from_factory`].
+    Restart(RestartPolicy),
+    /// Leave the child stopped and keep the others running. The group stops once none of its children are running.
+    Ignore,
+}
+
+/// The kind of wakeup the group supervisor receives
+enum GroupEvent {
+    ChildExited,
+    Stop,
+}
+
+// *** ChildService ***
+
+/// A service app run as part of a [`ServiceGroup`], along with the services it depends on and what to do
+/// when it stops running on its own.
+pub struct ChildService {
+    name: String,
+    app: Option<Box<dyn ServiceApp + Send>>,
+    factory: Option<ServiceFactory>,
+    dependencies: Vec<String>,
+    exit_policy: ChildExitPolicy,
+    // Set while the current instance has been started and not yet stopped
+    started_at: Option<Instant>,
+    // Whether the current instance reports when it exits, else it is polled
+    notifies_exit: bool,
+    failures: u32,
+    restart_at: Option<Instant>,
+    // The error of the child that caused the group to stop
+    failure: Option<Box<dyn std::error::Error + Send + Sync>>,
+}
+
+impl ChildService {
+    /// Creates a new child service from a service app. It can't be restarted, as only a single instance exists.
+    pub fn new(app: impl ServiceApp + Send + 'static) -> Self {
+        Self::from_app(Box::new(app), None)
+    }
+
+    /// Creates a new child service from a factory, which is called to create the initial instance and then
+    /// again each time the child is restarted. See [`ChildExitPolicy::Restart`].
+    pub fn from_factory(
+        mut factory: impl FnMut() -> Box<dyn ServiceApp + Send> + Send + 'static,
+    ) -> Self {
+        let app = factory();
+        Self::from_app(app, Some(Box::new(factory)))
+    }
+
+    fn from_app(app: Box<dyn ServiceApp + Send>, factory: Option<ServiceFactory>) -> Self {
+        Self {
+            name: app.name().to_string(),
+            app: Some(app),
+            factory,
+            dependencies: Vec::new(),
+            exit_policy: ChildExitPolicy::default(),
+            started_at: None,
+            notifies_exit: false,
+            failures: 0,
+            restart_at: None,
+            failure: None,
+        }
+    }
+
+    /// Sets the names of the services in the group that must be started before this one (and stopped after it).
+    pub fn with_dependencies<S: Into<String>>(
+        mut self,
+        dependencies: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.dependencies = dependencies.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets what the group does when this child stops running on its own.
+    pub fn with_exit_policy(mut self, exit_policy: ChildExitPolicy) -> Self {
+        self.exit_policy = exit_policy;
+        self
+    }
+
+    /// Returns the name of the child service.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self, notifier: &ExitNotifier) -> Result<()> {
+        let app = self
+            .app
+            .as_mut()
+            .ok_or_else(|| format!("Service '{}' not found", self.name))?;
+        self.notifies_exit = app.set_exit_notifier(notifier.clone());
+        app.start()?;
+        self.started_at = Some(Instant::now());
+        Ok(())
+    }
+
+    fn stop(&mut self, reason: ShutdownReason) -> Result<()> {
+        self.restart_at = None;
+        match (self.started_at.take(), self.app.take()) {
+            (Some(_), Some(app)) => app.stop_with_reason(reason),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.started_at.is_some() && self.app.as_ref().is_some_and(|app| app.is_running())
+    }
+
+    // Takes the instance out if it stopped running on its own, along with when it was started, so it can be
+    // stopped without holding the group's lock
+    fn take_exited(&mut self) -> Option<(Box<dyn ServiceApp + Send>, Instant)> {
+        if self.started_at.is_none() || self.is_running() {
+            return None;
+        }
+        Some((self.app.take()?, self.started_at.take()?))
+    }
+
+    // Applies the exit policy once the instance that stopped running was stopped with `result`. Returns
+    // `false` if the whole group should be stopped
+    fn handle_exit(&mut self, group: &str, started_at: Instant, result: Result<()>) -> bool {
+        match self.exit_policy.clone() {
+            ChildExitPolicy::StopAll => {
+                tracing::warn!(
+                    "Service '{}' in group '{group}' stopped running. Stopping the group...",
+                    self.name
+                );
+                self.failure = result.err();
+                false
+            }
+            ChildExitPolicy::Ignore => {
+                match result {
+                    Ok(()) => tracing::info!(
+                        "Service '{}' in group '{group}' stopped running. Ignoring it",
+                        self.name
+                    ),
+                    Err(err) => tracing::warn!(
+                        "Service '{}' in group '{group}' failed: {err}. Ignoring it",
+                        self.name
+                    ),
+                }
+                true
+            }
+            ChildExitPolicy::Restart(policy) => match result {
+                // A child that finished cleanly is done, like one that is ignored
+                Ok(()) => {
+                    tracing::info!(
+                        "Service '{}' in group '{group}' finished. Not restarting it",
+                        self.name
+                    );
+                    true
+                }
+                Err(err) => {
+                    if started_at.elapsed() >= policy.reset_window() {
+                        self.failures = 0;
+                    }
+                    tracing::warn!("Service '{}' in group '{group}' failed: {err}", self.name);
+                    self.failure = Some(err);
+                    self.schedule_restart(group, &policy)
+                }
+            },
+        }
+    }
+
+    fn schedule_restart(&mut self, group: &str, policy: &RestartPolicy) -> bool {
+        self.failures += 1;
+        match policy.backoff(self.failures) {
+            Some(backoff) => {
+                tracing::warn!(
+                    "Restarting service '{}' in group '{group}' in {backoff:?} (restart {})...",
+                    self.name,
+                    self.failures
+                );
+                self.failure = None;
+                self.restart_at = Some(Instant::now() + backoff);
+                true
+            }
+            None => {
+                tracing::error!(
+                    "Service '{}' in group '{group}' stopped running {} times in a row. Stopping the group...",
+                    self.name,
+                    self.failures
+                );
+                false
+            }
+        }
+    }
+
+    // Returns `false` if the whole group should be stopped
+    fn restart_if_due(&mut self, group: &str, notifier: &ExitNotifier) -> bool {
+        match self.restart_at {
+            Some(restart_at) if Instant::now() >= restart_at => self.restart(group, notifier),
+            _ => true,
+        }
+    }
+
+    fn restart(&mut self, group: &str, notifier: &ExitNotifier) -> bool {
+        self.restart_at = None;
+        let ChildExitPolicy::Restart(policy) = self.exit_policy.clone() else {
+            return true;
+        };
+        let Some(factory) = &mut self.factory else {
+            return true;
+        };
+
+        tracing::info!("Restarting service '{}' in group '{group}'...", self.name);
+        self.app = Some(factory());
+        if let Err(err) = self.start(notifier) {
+            tracing::warn!(
+                "Service '{}' in group '{group}' could not be restarted: {err}",
+                self.name
+            );
+            self.app = None;
+            self.failure = Some(err);
+            return self.schedule_restart(group, &policy);
+        }
+        true
+    }
+}
+
+// *** SharedStartHook ***
+
+/// Splits the start hook of the group between its children. Each child runs its part once it has acquired
+/// its resources, and the last part to run runs the hook itself, so it only runs once every child is ready.
+/// The other parts wait for its result. A part that is dropped without running counts as ready.
+struct SharedStartHook {
+    state: Mutex<StartHookState>,
+    done: Condvar,
+}
+
+struct StartHookState {
+    hook: Option<StartHook>,
+    // The parts that are not ready yet, plus the group itself while it hands them out
+    pending: usize,
+    // Set once the hook ran or was cancelled. Errors are kept as messages, as each part returns them
+    result: Option<std::result::Result<(), String>>,
+}
+
+impl SharedStartHook {
+    fn new(hook: StartHook, parts: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(StartHookState {
+                hook: Some(hook),
+                pending: parts + 1,
+                result: None,
+            }),
+            done: Condvar::new(),
+        })
+    }
+
+    fn part(self: &Arc<Self>) -> StartHook {
+        let part = StartHookPart(Some(self.clone()));
+        Box::new(move || part.run())
+    }
+
+    // Runs the hook if this was the last one not ready
+    fn mark_ready(&self) -> MutexGuard<'_, StartHookState> {
+        let mut state = self.state.lock().expect("Mutex poisoned");
+        state.pending -= 1;
+        if state.pending == 0
+            && let Some(hook) = state.hook.take()
+        {
+            state.result = Some(hook().map_err(|err| err.to_string()));
+            self.done.notify_all();
+        }
+        state
+    }
+
+    fn run_part(&self) -> Result<()> {
+        let state = self.mark_ready();
+        let state = self
+            .done
+            .wait_while(state, |state| state.result.is_none())
+            .expect("Mutex poisoned");
+        match &state.result {
+            Some(Err(err)) => Err(err.clone().into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Releases the waiting parts without running the hook, as the children are being stopped
+    fn cancel(&self) {
+        let mut state = self.state.lock().expect("Mutex poisoned");
+        state.hook = None;
+        if state.result.is_none() {
+            state.result = Some(Err(
+                "The service group was stopped before its start hook ran".into(),
+            ));
+        }
+        self.done.notify_all();
+    }
+}
+
+/// The part of the start hook of the group handed to a child
+struct StartHookPart(Option<Arc<SharedStartHook>>);
+
+impl StartHookPart {
+    fn run(mut self) -> Result<()> {
+        match self.0.take() {
+            Some(shared) => shared.run_part(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for StartHookPart {
+    fn drop(&mut self) {
+        if let Some(shared) = self.0.take() {
+            drop(shared.mark_ready());
+        }
+    }
+}
+
+// *** ServiceGroup ***
+
+struct GroupState {
+    // In start order once the group is started
+    children: Vec<ChildService>,
+    running: bool,
+}
+
+impl GroupState {
+    // Orders the children so each one comes after its dependencies, keeping the order they were added in otherwise
+    fn sort_children(&mut self) -> Result<()> {
+        {
+            let mut names = HashSet::new();
+            for child in &self.children {
+                if !names.insert(child.name.as_str()) {
+                    return Err(format!("Service '{}' was added more than once", child.name).into());
+                }
+                if matches!(child.exit_policy, ChildExitPolicy::Restart(_))
+                    && child.factory.is_none()
+                {
+                    return Err(format!(
+                        "Service '{}' can't be restarted without a factory",
+                        child.name
+                    )
+                    .into());
+                }
+            }
+            for child in &self.children {
+                if let Some(dependency) = child
+                    .dependencies
+                    .iter()
+                    .find(|dependency| !names.contains(dependency.as_str()))
+                {
+                    return Err(format!(
+                        "Service '{}' depends on unknown service '{dependency}'",
+                        child.name
+                    )
+                    .into());
+                }
+            }
+        }
+
+        let mut remaining = mem::take(&mut self.children);
+        let mut sorted: Vec<ChildService> = Vec::with_capacity(remaining.len());
+
+        while !remaining.is_empty() {
+            let next = remaining.iter().position(|child| {
+                child
+                    .dependencies
+                    .iter()
+                    .all(|dependency| sorted.iter().any(|sorted| &sorted.name == dependency))
+            });
+            match next {
+                Some(next) => sorted.push(remaining.remove(next)),
+                None => {
+                    let names: Vec<_> = remaining.iter().map(|child| child.name.as_str()).collect();
+                    let err = format!("Dependency cycle between services: {}", names.join(", "));
+                    sorted.append(&mut remaining);
+                    self.children = sorted;
+                    return Err(err.into());
+                }
+            }
+        }
+
+        self.children = sorted;
+        Ok(())
+    }
+
+    // How long the supervisor can wait for a child to report it exited: until the next restart is due, and
+    // no longer than the poll interval while a running child can't report it. `None` if it can wait forever
+    fn supervise_timeout(&self) -> Option<Duration> {
+        let poll = self
+            .children
+            .iter()
+            .any(|child| child.started_at.is_some() && !child.notifies_exit)
+            .then_some(POLL_INTERVAL);
+        let restart = self
+            .children
+            .iter()
+            .filter_map(|child| child.restart_at)
+            .min()
+            .map(|restart_at| restart_at.saturating_duration_since(Instant::now()));
+        poll.into_iter().chain(restart).min()
+    }
+
+    // Stops the children in reverse start order. The error of the child that caused the group to stop is
+    // returned first, else the first error from stopping a child
+    fn stop_children(&mut self, group: &str, reason: ShutdownReason) -> Result<()> {
+        let mut result = match self
+            .children
+            .iter_mut()
+            .find_map(|child| child.failure.take())
+        {
+            Some(err) => Err(err),
+            None => Ok(()),
+        };
+
+        for child in self.children.iter_mut().rev() {
+            if let Err(err) = child.stop(reason) {
+                tracing::error!("Service '{}' in group '{group}' failed: {err}", child.name);
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        self.running = false;
+        result
+    }
+}
+
+/// A service app that runs several child service apps in one process. The children are started in
+/// dependency order and stopped in reverse order. What happens when a child stops running on its own is
+/// set per child with a [`ChildExitPolicy`].
+///
+/// Sockets passed in by the service manager can only be taken once per process, so they all go to the
+/// first child that takes them (the first [`BaseService`](crate::BaseService) started). Put the child that
+/// uses socket activation first in dependency order, or take the sockets yourself and hand them out.
+pub struct ServiceGroup {
+    name: String,
+    state: Arc<Mutex<GroupState>>,
+    exit_notifier: Option<ExitNotifier>,
+    event_sender: Option<Sender<GroupEvent>>,
+    supervisor: Option<JoinHandle<()>>,
+    start_hook: Option<Arc<SharedStartHook>>,
+}
+
+impl ServiceGroup {
+    /// Creates a new, empty service group.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            state: Arc::new(Mutex::new(GroupState {
+                children: Vec::new(),
+                running: false,
+            })),
+            exit_notifier: None,
+            event_sender: None,
+            supervisor: None,
+            start_hook: None,
+        }
+    }
+
+    /// Adds a service app with no dependencies that stops the group when it stops running.
+    pub fn with_service(self, app: impl ServiceApp + Send + 'static) -> Self {
+        self.with_child(ChildService::new(app))
+    }
+
+    /// Adds a child service.
+    pub fn with_child(self, child: ChildService) -> Self {
+        self.state
+            .lock()
+            .expect("Mutex poisoned")
+            .children
+            .push(child);
+        self
+    }
+
+    // Applies `action_fn` to all running children, logging each error (`action` is what they could not do).
+    // The first error is returned.
+    fn for_each_running(
+        &self,
+        action: &str,
+        action_fn: impl Fn(&mut Box<dyn ServiceApp + Send>) -> Result<()>,
+    ) -> Result<()> {
+        let mut state = self.state.lock().expect("Mutex poisoned");
+        let mut result = Ok(());
+
+        for child in state.children.iter_mut().filter(|child| child.is_running()) {
+            if let Some(app) = &mut child.app
+                && let Err(err) = action_fn(app)
+            {
+                tracing::error!(
+                    "Service '{}' in group '{}' could not {action}: {err}",
+                    child.name,
+                    self.name
+                );
+                if result.is_ok() {
+                    result = Err(err);
+                }
+            }
+        }
+        result
+    }
+
+    // Children waiting for the others to be ready would otherwise never stop
+    fn cancel_start_hook(&self) {
+        if let Some(start_hook) = &self.start_hook {
+            start_hook.cancel();
+        }
+    }
+
+    fn stop_supervisor(&mut self) {
+        if let Some(event_sender) = self.event_sender.take() {
+            // If the supervisor is gone, it already stopped on its own
+            let _ = event_sender.send(GroupEvent::Stop);
+        }
+        if let Some(supervisor) = self.supervisor.take()
+            && supervisor.join().is_err()
+        {
+            tracing::error!("Supervisor of service group '{}' panicked", self.name);
+        }
+    }
+}
+
+impl ServiceApp for ServiceGroup {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self) -> Result<()> {
+        tracing::info!("Starting service group '{}'...", self.name);
+
+        let (event_sender, event_receiver) = channel();
+        let notifier = {
+            let event_sender = event_sender.clone();
+            ExitNotifier::new(move || {
+                // If the receiver is gone, the group is no longer supervising
+                let _ = event_sender.send(GroupEvent::ChildExited);
+            })
+        };
+
+        {
+            let mut state = self.state.lock().expect("Mutex poisoned");
+            state.sort_children()?;
+
+            for idx in 0..state.children.len() {
+                if let Err(err) = state.children[idx].start(&notifier) {
+                    tracing::error!(
+                        "Service '{}' in group '{}' could not be started: {err}",
+                        state.children[idx].name,
+                        self.name
+                    );
+                    self.cancel_start_hook();
+                    // Any error stopping the others is logged
+                    let _ = state.stop_children(&self.name, ShutdownReason::Programmatic);
+                    return Err(err);
+                }
+            }
+            state.running = true;
+        }
+
+        let name = self.name.clone();
+        let state = self.state.clone();
+        let exit_notifier = self.exit_notifier.clone();
+        self.event_sender = Some(event_sender);
+        self.supervisor = Some(thread::spawn(move || {
+            supervise(&name, &state, event_receiver, &notifier, exit_notifier)
+        }));
+        Ok(())
+    }
+
+    fn stop(self: Box<Self>) -> Result<()> {
+        self.stop_with_reason(ShutdownReason::Programmatic)
+    }
+
+    fn stop_with_reason(mut self: Box<Self>, reason: ShutdownReason) -> Result<()> {
+        tracing::info!("Stopping service group '{}' ({reason})...", self.name);
+        self.stop_supervisor();
+        self.cancel_start_hook();
+
+        let result = self
+            .state
+            .lock()
+            .expect("Mutex poisoned")
+            .stop_children(&self.name, reason);
+        tracing::info!("Service group '{}' is shut down.", self.name);
+        result
+    }
+
+    fn is_running(&self) -> bool {
+        self.state.lock().expect("Mutex poisoned").running
+    }
+
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.exit_notifier = Some(notifier);
+        true
+    }
+
+    // Each child gets a part of the hook, so it only runs once all of them are ready. Restarted children
+    // don't run it again.
+    fn set_start_hook(&mut self, hook: StartHook) -> bool {
+        let mut state = self.state.lock().expect("Mutex poisoned");
+        let shared = SharedStartHook::new(hook, state.children.len());
+
+        let mut forwarded = false;
+        for child in &mut state.children {
+            let part = shared.part();
+            if let Some(app) = &mut child.app {
+                forwarded |= app.set_start_hook(part);
+            }
+        }
+        if !forwarded {
+            return false;
+        }
+        drop(shared.mark_ready());
+        self.start_hook = Some(shared);
+        true
+    }
+
+    // The children are stopped one after the other
+    fn stop_timeout(&self) -> Option<Duration> {
+        self.state
+            .lock()
+            .expect("Mutex poisoned")
+            .children
+            .iter()
+            .filter_map(|child| child.app.as_ref()?.stop_timeout())
+            .reduce(|total, timeout| total + timeout)
+    }
+
+    // Each running child is reported as a component
+    fn health(&self) -> HealthReport {
+        let state = self.state.lock().expect("Mutex poisoned");
+        let running = state.children.iter().filter(|child| child.is_running());
+        running
+            .filter_map(|child| child.app.as_ref())
+            .fold(HealthReport::ok(), |report, app| {
+                let health = app.health();
+                report.with_component(app.name(), health.state(), unhealthy_components(&health))
+            })
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.for_each_running("be reloaded", |app| app.reload())
+    }
+
+    // Only the children that support pausing are paused
+    fn can_pause(&self) -> bool {
+        self.state
+            .lock()
+            .expect("Mutex poisoned")
+            .children
+            .iter()
+            .any(|child| child.app.as_ref().is_some_and(|app| app.can_pause()))
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.for_each_running("be paused", |app| match app.can_pause() {
+            true => app.pause(),
+            false => Ok(()),
+        })
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        self.for_each_running("be resumed", |app| match app.can_pause() {
+            true => app.resume(),
+            false => Ok(()),
+        })
+    }
+
+    // Sent to all children, so each ignores the commands it doesn't know
+    fn command(&mut self, command: u32) -> Result<()> {
+        self.for_each_running(&format!("run command {command}"), |app| {
+            app.command(command)
+        })
+    }
+}
+
+impl Drop for ServiceGroup {
+    fn drop(&mut self) {
+        self.stop_supervisor();
+    }
+}
+
+// Wakes up when a child reports it exited (children that can't report it are polled) or a restart is due,
+// and applies the child's exit policy. Children that exited are stopped without holding the lock. It stops
+// once asked to, or once the group has stopped running
+fn supervise(
+    group: &str,
+    state: &Mutex<GroupState>,
+    event_receiver: Receiver<GroupEvent>,
+    notifier: &ExitNotifier,
+    exit_notifier: Option<ExitNotifier>,
+) {
+    let mut timeout = state.lock().expect("Mutex poisoned").supervise_timeout();
+    loop {
+        let event = match timeout {
+            Some(timeout) => event_receiver.recv_timeout(timeout),
+            None => event_receiver
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match event {
+            Ok(GroupEvent::ChildExited) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(GroupEvent::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let exited: Vec<_> = state
+            .lock()
+            .expect("Mutex poisoned")
+            .children
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, child)| Some((idx, child.take_exited()?)))
+            .collect();
+        let stopped: Vec<_> = exited
+            .into_iter()
+            .map(|(idx, (app, started_at))| {
+                (
+                    idx,
+                    started_at,
+                    app.stop_with_reason(ShutdownReason::AppExited),
+                )
+            })
+            .collect();
+
+        let mut state = state.lock().expect("Mutex poisoned");
+        let mut keep_running = true;
+        for (idx, started_at, result) in stopped {
+            keep_running &= state.children[idx].handle_exit(group, started_at, result);
+        }
+        if keep_running {
+            keep_running = state
+                .children
+                .iter_mut()
+                .all(|child| child.restart_if_due(group, notifier));
+        }
+        if keep_running {
+            keep_running = state
+                .children
+                .iter()
+                .any(|child| child.started_at.is_some() || child.restart_at.is_some());
+        }
+
+        if !keep_running {
+            state.running = false;
+            if let Some(exit_notifier) = &exit_notifier {
+                exit_notifier.notify();
+            }
+            return;
+        }
+        timeout = state.supervise_timeout();
+    }
+}
+
+// The message of a child's component: its own components that are not ok, as they would be displayed
+fn unhealthy_components(report: &HealthReport) -> String {
+    report
+        .components()
+        .iter()
+        .filter(|component| component.state() != HealthState::Ok)
+        .map(|component| match component.message() {
+            "" => component.name().to_string(),
+            message => format!("{} ({message})", component.name()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,162 @@
+use std::{
+    fmt,
+    sync::{
+        Arc, Mutex,
+        mpsc::{Sender, channel},
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{ControlEvent, Result, RunOptions, ServiceApp, ShutdownReason, run_app};
+
+/// The state of a service run with [`spawn_service`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServiceState {
+    /// The service is being started
+    Starting,
+    /// The service has started and is running
+    Running,
+    /// The service is being paused
+    Pausing,
+    /// The service is paused. See [`ServiceApp::pause`].
+    Paused,
+    /// The service is being resumed
+    Resuming,
+    /// The service is being stopped
+    Stopping,
+    /// The service has stopped (or failed to start). Use [`ServiceHandle::join`] to get its result.
+    Stopped,
+}
+
+impl fmt::Display for ServiceState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            ServiceState::Starting => "starting",
+            ServiceState::Running => "running",
+            ServiceState::Pausing => "pausing",
+            ServiceState::Paused => "paused",
+            ServiceState::Resuming => "resuming",
+            ServiceState::Stopping => "stopping",
+            ServiceState::Stopped => "stopped",
+        };
+        f.write_str(state)
+    }
+}
+
+/// A handle to a service run with [`spawn_service`], used to control it in place of OS signals.
+pub struct ServiceHandle {
+    name: String,
+    can_pause: bool,
+    control_tx: Sender<ControlEvent>,
+    state: Arc<Mutex<ServiceState>>,
+    thread: JoinHandle<Result<()>>,
+}
+
+impl ServiceHandle {
+    /// Returns the name of the service.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Asks the service to stop (with [`ShutdownReason::Programmatic`]). It returns immediately, so use
+    /// [`join`](Self::join) to wait for the service to stop. Asking a service that already stopped does nothing.
+    pub fn stop(&self) {
+        // If the receiver is gone, the service already stopped
+        let _ = self
+            .control_tx
+            .send(ControlEvent::Shutdown(ShutdownReason::Programmatic));
+    }
+
+    /// Asks the service to reload its configuration. See [`ServiceApp::reload`]. An error is returned if the
+    /// service is not running.
+    pub fn reload(&self) -> Result<()> {
+        self.send_if(ServiceState::Running, ControlEvent::Reload, "running")
+    }
+
+    /// Asks the service to pause. See [`ServiceApp::pause`]. It returns immediately, so use
+    /// [`status`](Self::status) to find out when the service is paused. An error is returned if the service
+    /// does not support pausing or is not running.
+    pub fn pause(&self) -> Result<()> {
+        if !self.can_pause {
+            return Err(format!("Service '{}' does not support pausing", self.name).into());
+        }
+        self.send_if(ServiceState::Running, ControlEvent::Pause, "running")
+    }
+
+    /// Asks a paused service to resume. See [`ServiceApp::resume`]. It returns immediately, so use
+    /// [`status`](Self::status) to find out when the service is running again. An error is returned if the
+    /// service is not paused.
+    pub fn resume(&self) -> Result<()> {
+        self.send_if(ServiceState::Paused, ControlEvent::Resume, "paused")
+    }
+
+    /// Sends an application-defined command to the service. See [`ServiceApp::command`]. It returns
+    /// immediately. An error is returned if the service is not running or paused.
+    pub fn send_command(&self, command: u32) -> Result<()> {
+        let not_running = || format!("Service '{}' is not running", self.name).into();
+        if !matches!(self.status(), ServiceState::Running | ServiceState::Paused) {
+            return Err(not_running());
+        }
+        self.control_tx
+            .send(ControlEvent::Command(command))
+            .map_err(|_| not_running())
+    }
+
+    /// Returns the current state of the service.
+    pub fn status(&self) -> ServiceState {
+        *self.state.lock().expect("Mutex poisoned")
+    }
+
+    /// Waits for the service to stop and returns its result, as [`run_service`](crate::run_service) would.
+    pub fn join(self) -> Result<()> {
+        self.thread.join().map_err(|_| "Error joining thread")?
+    }
+
+    // Sends `event` if the service is in `state` (described as `expected` in the error otherwise)
+    fn send_if(&self, state: ServiceState, event: ControlEvent, expected: &str) -> Result<()> {
+        let not_expected = || format!("Service '{}' is not {expected}", self.name).into();
+        if self.status() != state {
+            return Err(not_expected());
+        }
+        self.control_tx.send(event).map_err(|_| not_expected())
+    }
+}
+
+/// Runs a service in interactive mode on a new thread and returns immediately with a handle to control it.
+/// Unlike [`run_service`](crate::run_service), no OS signal handlers are installed, so the service is only
+/// stopped through the handle (or when it exits on its own). This is useful to host a service in a GUI or a
+/// test harness.
+pub fn spawn_service(app: impl ServiceApp + Send + 'static) -> Result<ServiceHandle> {
+    let name = app.name().to_string();
+    let can_pause = app.can_pause();
+    let state = Arc::new(Mutex::new(ServiceState::Starting));
+    let (control_tx, control_rx) = channel();
+
+    let thread = {
+        let control_tx = control_tx.clone();
+        let state = state.clone();
+        thread::Builder::new()
+            .name(format!("{name}-runtime"))
+            .spawn(move || {
+                let set_state = |new_state| *state.lock().expect("Mutex poisoned") = new_state;
+                let result = run_app(
+                    Box::new(app),
+                    control_tx,
+                    control_rx,
+                    &RunOptions::default(),
+                    set_state,
+                );
+                set_state(ServiceState::Stopped);
+                result
+            })?
+    };
+
+    Ok(ServiceHandle {
+        name,
+        can_pause,
+        control_tx,
+        state,
+        thread,
+    })
+}
@@ -0,0 +1,226 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{RunOptions, notify};
+
+// How often the health is checked when only a failing timeout is set
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// *** HealthState ***
+
+/// The overall health of a service or of one of its components, from best to worst
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HealthState {
+    /// Working as expected
+    #[default]
+    Ok,
+    /// Working, but with reduced capacity or performance (for example, a cache or replica is unavailable)
+    Degraded,
+    /// Not doing its work, even though it is still running
+    Failing,
+}
+
+impl fmt::Display for HealthState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HealthState::Ok => "ok",
+            HealthState::Degraded => "degraded",
+            HealthState::Failing => "failing",
+        })
+    }
+}
+
+// *** ComponentHealth ***
+
+/// The result of a named health check within a [`HealthReport`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ComponentHealth {
+    name: String,
+    state: HealthState,
+    message: String,
+}
+
+impl ComponentHealth {
+    /// Returns the name of the component.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the health of the component.
+    pub fn state(&self) -> HealthState {
+        self.state
+    }
+
+    /// Returns the message explaining the health of the component (possibly empty).
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+// *** HealthReport ***
+
+/// A structured health report returned by [`ServiceApp::health`](crate::ServiceApp::health). Its state is the
+/// worst of the state it was created with and the state of each of its components.
+///
+/// It displays as its state followed by the components that are not ok, for example
+/// `degraded: database (replica lag is 30s)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HealthReport {
+    state: HealthState,
+    components: Vec<ComponentHealth>,
+}
+
+impl HealthReport {
+    /// Creates a report with the given overall state and no components.
+    pub fn new(state: HealthState) -> Self {
+        Self {
+            state,
+            components: Vec::new(),
+        }
+    }
+
+    /// Creates a healthy report with no components.
+    pub fn ok() -> Self {
+        Self::new(HealthState::Ok)
+    }
+
+    /// Adds the result of the health check of the component `name`, with a message explaining it (possibly
+    /// empty).
+    pub fn with_component(
+        mut self,
+        name: impl Into<String>,
+        state: HealthState,
+        message: impl Into<String>,
+    ) -> Self {
+        self.components.push(ComponentHealth {
+            name: name.into(),
+            state,
+            message: message.into(),
+        });
+        self
+    }
+
+    /// Returns the overall health: the worst of the state the report was created with and the state of each
+    /// of its components.
+    pub fn state(&self) -> HealthState {
+        self.components
+            .iter()
+            .map(ComponentHealth::state)
+            .fold(self.state, HealthState::max)
+    }
+
+    /// Returns the components that were checked.
+    pub fn components(&self) -> &[ComponentHealth] {
+        &self.components
+    }
+}
+
+impl fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.state())?;
+
+        let unhealthy = self
+            .components
+            .iter()
+            .filter(|component| component.state != HealthState::Ok);
+        for (i, component) in unhealthy.enumerate() {
+            f.write_str(if i == 0 { ": " } else { ", " })?;
+            f.write_str(&component.name)?;
+            if !component.message.is_empty() {
+                write!(f, " ({})", component.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// *** HealthMonitor ***
+
+/// Checks the health of the app periodically (if enabled), reporting changes to the service manager, and
+/// tracks how long it has been failing
+pub(crate) struct HealthMonitor {
+    interval: Option<Duration>,
+    failing_timeout: Option<Duration>,
+    next_check: Option<Instant>,
+    state: HealthState,
+    failing_since: Option<Instant>,
+}
+
+impl HealthMonitor {
+    pub(crate) fn new(options: &RunOptions) -> Self {
+        // A failing timeout can only be noticed by checking
+        let interval = match (options.health_check_interval, options.failing_timeout) {
+            (Some(interval), _) => Some(interval),
+            (None, Some(_)) => Some(HEALTH_CHECK_INTERVAL),
+            (None, None) => None,
+        };
+        Self {
+            interval,
+            failing_timeout: options.failing_timeout,
+            next_check: interval.map(|interval| Instant::now() + interval),
+            state: HealthState::Ok,
+            failing_since: None,
+        }
+    }
+
+    /// The time remaining until the next check is due, if the health is checked periodically
+    pub(crate) fn time_until_check(&self) -> Option<Duration> {
+        self.next_check
+            .map(|next_check| next_check.saturating_duration_since(Instant::now()))
+    }
+
+    /// Checks the health of the app if a check is due. Returns `true` if the app has been failing for longer
+    /// than allowed, and so should be stopped.
+    pub(crate) fn check_if_due(
+        &mut self,
+        name: &str,
+        health: impl FnOnce() -> HealthReport,
+    ) -> bool {
+        let (Some(next_check), Some(interval)) = (self.next_check, self.interval) else {
+            return false;
+        };
+        if Instant::now() < next_check {
+            return false;
+        }
+        self.next_check = Some(Instant::now() + interval);
+        self.update(name, &health())
+    }
+
+    /// Records the health of the app (checked by the caller). Returns `true` if the app has been failing for
+    /// longer than allowed.
+    pub(crate) fn update(&mut self, name: &str, report: &HealthReport) -> bool {
+        let state = report.state();
+        if state != self.state {
+            match state {
+                HealthState::Ok => tracing::info!("Service '{name}' health changed: {report}"),
+                _ => tracing::warn!("Service '{name}' health changed: {report}"),
+            }
+            if let Err(err) = notify::notify_status(&status_line(report)) {
+                tracing::warn!("Could not send the status to the service manager: {err}");
+            }
+            self.state = state;
+        }
+
+        match (state, self.failing_since) {
+            (HealthState::Failing, None) => self.failing_since = Some(Instant::now()),
+            (HealthState::Failing, Some(_)) => {}
+            _ => self.failing_since = None,
+        }
+        match (self.failing_since, self.failing_timeout) {
+            (Some(since), Some(timeout)) => since.elapsed() >= timeout,
+            _ => false,
+        }
+    }
+}
+
+// The status must be a single line
+fn status_line(report: &HealthReport) -> String {
+    let status = report.to_string().replace(['\r', '\n'], " ");
+    let mut chars = status.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => status,
+    }
+}
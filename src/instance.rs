@@ -0,0 +1,168 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    ExitNotifier, HealthReport, Result, ServiceApp, ShutdownReason, StartHook,
+    pid_file::{LockedError, PidFile},
+};
+
+/// A service app that refuses to start while another copy of the wrapped app is running on the same machine
+/// (for example, an interactive debug run while the installed service is running). While the app runs, an
+/// advisory lock is held on a file named after [`ServiceApp::name`] holding the PID of the process. The file
+/// is kept in the runtime directory shared by all users (`/run/lock` on Linux, `/tmp` on other Unix systems
+/// and `%ProgramData%\uni_service` on Windows), unless set otherwise. On Unix, a lock file that is a symbolic
+/// link, or that is owned by another user, is refused rather than written to.
+pub struct SingleInstance<A> {
+    app: Option<A>,
+    lock_dir: Option<PathBuf>,
+    lock: Option<PidFile>,
+}
+
+impl<A: ServiceApp> SingleInstance<A> {
+    /// Wraps `app`, holding the instance lock while it runs.
+    pub fn new(app: A) -> Self {
+        Self {
+            app: Some(app),
+            lock_dir: None,
+            lock: None,
+        }
+    }
+
+    /// Keeps the lock file in `dir` (created if it does not exist) instead of the runtime directory. All copies
+    /// of the app must use the same directory.
+    pub fn with_lock_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.lock_dir = Some(dir.into());
+        self
+    }
+
+    /// Returns the path of the lock file.
+    pub fn lock_path(&self) -> PathBuf {
+        let dir = self.lock_dir.clone().unwrap_or_else(runtime_dir);
+        let name: String = self
+            .name()
+            .chars()
+            .map(|ch| match ch {
+                '/' | '\\' | ':' => '_',
+                ch => ch,
+            })
+            .collect();
+        dir.join(format!("{name}.lock"))
+    }
+
+    fn app(&self) -> &A {
+        self.app.as_ref().expect("Service app not found")
+    }
+
+    fn app_mut(&mut self) -> &mut A {
+        self.app.as_mut().expect("Service app not found")
+    }
+
+    fn lock(&self, path: &Path) -> Result<PidFile> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        PidFile::create(path).map_err(|err| match err.downcast::<LockedError>() {
+            Ok(err) => format!("Service '{}' is already running: {err}", self.name()).into(),
+            Err(err) => err,
+        })
+    }
+}
+
+impl<A: ServiceApp> ServiceApp for SingleInstance<A> {
+    fn name(&self) -> &str {
+        self.app().name()
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let path = self.lock_path();
+        let lock = self.lock(&path)?;
+        tracing::debug!("Instance lock '{}' acquired", path.display());
+
+        // If the app fails to start, the lock is released when dropped
+        self.app_mut().start()?;
+        self.lock = Some(lock);
+        Ok(())
+    }
+
+    fn stop(self: Box<Self>) -> Result<()> {
+        self.stop_with_reason(ShutdownReason::Programmatic)
+    }
+
+    // The lock is only released once the app has fully stopped
+    fn stop_with_reason(mut self: Box<Self>, reason: ShutdownReason) -> Result<()> {
+        let result = match self.app.take() {
+            Some(app) => Box::new(app).stop_with_reason(reason),
+            None => Ok(()),
+        };
+        self.lock = None;
+        result
+    }
+
+    fn is_running(&self) -> bool {
+        self.app.as_ref().is_some_and(|app| app.is_running())
+    }
+
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.app_mut().set_exit_notifier(notifier)
+    }
+
+    fn set_start_hook(&mut self, hook: StartHook) -> bool {
+        self.app_mut().set_start_hook(hook)
+    }
+
+    fn stop_timeout(&self) -> Option<Duration> {
+        self.app.as_ref().and_then(|app| app.stop_timeout())
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.app.as_ref().is_none_or(|app| app.is_healthy())
+    }
+
+    fn health(&self) -> HealthReport {
+        self.app
+            .as_ref()
+            .map_or_else(HealthReport::ok, |app| app.health())
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.app_mut().reload()
+    }
+
+    fn can_pause(&self) -> bool {
+        self.app.as_ref().is_some_and(|app| app.can_pause())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.app_mut().pause()
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        self.app_mut().resume()
+    }
+
+    fn command(&mut self, command: u32) -> Result<()> {
+        self.app_mut().command(command)
+    }
+}
+
+// The lock must be visible to copies run by any user, so per-user directories can't be used
+fn runtime_dir() -> PathBuf {
+    #[cfg(target_os = "linux")]
+    if Path::new("/run/lock").is_dir() {
+        return PathBuf::from("/run/lock");
+    }
+    #[cfg(windows)]
+    if let Some(dir) = std::env::var_os("ProgramData") {
+        return PathBuf::from(dir).join("uni_service");
+    }
+
+    if cfg!(unix) {
+        PathBuf::from("/tmp")
+    } else {
+        std::env::temp_dir()
+    }
+}
@@ -2,25 +2,88 @@
 
 //! Universal service crate for building cross platform OS services
 
+mod activation;
+#[cfg(feature = "tokio")]
+mod async_service;
 mod base;
+mod console;
+mod context;
+#[cfg(unix)]
+mod control;
+#[cfg(unix)]
+mod daemon;
+mod exit;
+mod group;
+mod handle;
+mod health;
+mod instance;
+#[cfg(feature = "tracing-subscriber")]
+mod logging;
+mod mode;
+mod notify;
+mod panic;
+mod pid_file;
+#[cfg(unix)]
+mod privileges;
+mod restart;
+mod run_loop;
+mod shutdown;
+mod signals;
 #[doc = include_str!("../README.md")]
 mod readme_tests {}
 #[cfg(windows)]
 mod win_service;
 
-pub use base::BaseService;
+pub use activation::ListenFds;
+#[cfg(feature = "tokio")]
+pub use async_service::{
+    AsyncBaseService, AsyncServiceApp, run_service_async, run_service_async_with,
+};
+pub use base::{BaseService, RestartingFn, STOP_TIMEOUT_EXIT_CODE};
+pub use context::ServiceContext;
+#[cfg(unix)]
+pub use control::control_socket_path;
+#[cfg(unix)]
+pub use daemon::{DaemonOptions, run_service_daemon};
+pub use exit::{ExitCodeError, ExitNotifier, FAILURE_EXIT_CODE, exit_code};
+pub use group::{ChildExitPolicy, ChildService, ServiceFactory, ServiceGroup};
+pub use handle::{ServiceHandle, ServiceState, spawn_service};
+pub use health::{ComponentHealth, HealthReport, HealthState};
+pub use instance::SingleInstance;
+#[cfg(feature = "tracing-subscriber")]
+pub use logging::ServiceLogger;
+pub use mode::{detect_service_mode, run_service_auto};
+pub use notify::notify_status;
+pub use panic::{PANIC_EXIT_CODE, PanicError};
+#[cfg(target_os = "linux")]
+pub use privileges::Capability;
+#[cfg(unix)]
+pub use privileges::{Credentials, DropPrivileges};
+pub use restart::RestartPolicy;
+pub use shutdown::{Cancelled, ShutdownReason, ShutdownToken};
 
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::{
-    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    io::{self, IsTerminal as _},
+    ops::ControlFlow,
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel},
     time::Duration,
 };
 
+use run_loop::{AppCall, RunLoop, Step};
 #[cfg(windows)]
-use win_service::start_service;
+use win_service::{start_service, start_service_or_interactive};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 /// The result type for this crate. The error type is simply a boxed error trait object.
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
+/// A function run on the thread that runs a service, once the service has acquired the resources it needs
+/// privileges for. See [`ServiceApp::set_start_hook`].
+pub type StartHook = Box<dyn FnOnce() -> Result<()> + Send>;
+
 /// A service application.
 pub trait ServiceApp {
     /// Returns the name of the service.
@@ -33,52 +96,397 @@ pub trait ServiceApp {
     /// Called when the service is stopped. It should do any cleanup necessary and return.
     fn stop(self: Box<Self>) -> Result<()>;
 
+    /// Called instead of [`stop`](Self::stop) when the runtime stops the service, so the service can act
+    /// on why it is being stopped (for example, skipping an expensive flush on a Ctrl-C during development).
+    /// The default implementation ignores the reason and calls [`stop`](Self::stop).
+    fn stop_with_reason(self: Box<Self>, reason: ShutdownReason) -> Result<()> {
+        let _ = reason;
+        self.stop()
+    }
+
     /// Returns whether the service is currently running. If it returns `false`, the service
     /// itself will be stopped.
     fn is_running(&self) -> bool;
+
+    /// Called before [`start`](Self::start) with a notifier the app should use to report that it stopped
+    /// running on its own. If this returns `true`, the runtime relies on the notifier and stops polling
+    /// [`is_running`](Self::is_running), so it reacts immediately and stays asleep while idle. The default
+    /// implementation returns `false`, so `is_running` is polled instead.
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        let _ = notifier;
+        false
+    }
+
+    /// Called before [`start`](Self::start) with a hook the app should run on the thread that runs the
+    /// service, once the service has acquired the resources it needs privileges for (such as binding
+    /// privileged ports) and before it does anything else (for example, to drop privileges). If the hook
+    /// fails, the service should stop with the hook's error. If this returns `true`, the app runs the hook,
+    /// else the caller runs it once `start` returns. The default implementation returns `false`.
+    fn set_start_hook(&mut self, hook: StartHook) -> bool {
+        let _ = hook;
+        false
+    }
+
+    /// Returns how long the service may take to stop, if known. It is reported to the Windows service manager
+    /// as a hint while the service is stopping. The default implementation returns `None`.
+    fn stop_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns whether the service is healthy. It is only used by the default implementation of
+    /// [`health`](Self::health), so implement that instead for a detailed report. The default implementation
+    /// always returns `true`.
+    fn is_healthy(&self) -> bool {
+        true
+    }
+
+    /// Returns a report of the health of the service: an overall state and the result of any named component
+    /// checks. It can be checked periodically while the service is running (see
+    /// [`RunOptions::with_health_check_interval`]). Each change is logged and sent to the service manager as
+    /// the status (`STATUS=` on systemd). While the service is failing, the systemd watchdog (if enabled) is
+    /// no longer pinged, so a service that is running but hung gets restarted, and the service can also be
+    /// stopped with an error (see [`RunOptions::with_failing_timeout`]). A degraded service is still pinged.
+    /// The default implementation reports ok or failing according to [`is_healthy`](Self::is_healthy).
+    fn health(&self) -> HealthReport {
+        match self.is_healthy() {
+            true => HealthReport::ok(),
+            false => HealthReport::new(HealthState::Failing),
+        }
+    }
+
+    /// Called when the service is asked to reload its configuration (`SIGHUP` on Unix or a parameter
+    /// change request from the Windows service manager). An error is logged, but the service keeps
+    /// running. The default implementation does nothing.
+    fn reload(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Returns whether the service supports being paused. If it returns `false`, pause requests are refused,
+    /// and the Windows service manager is not told the service accepts them. The default implementation
+    /// returns `false`.
+    fn can_pause(&self) -> bool {
+        false
+    }
+
+    /// Called when the service is asked to pause (a pause request from the Windows service manager, a
+    /// configured signal on Unix or [`ServiceHandle::pause`]). The service should stop doing work until
+    /// [`resume`](Self::resume) is called, but remain ready to be stopped. If an error is returned, it is
+    /// logged and the service keeps running. It is only called if [`can_pause`](Self::can_pause) returns
+    /// `true`. The default implementation does nothing.
+    fn pause(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when a paused service is asked to resume its work. If an error is returned, it is logged and
+    /// the service stays paused. The default implementation does nothing.
+    fn resume(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the service is sent an application-defined command (for example, "rotate logs" or "dump
+    /// stats"), identified by `command`. Commands are sent by signals configured with
+    /// [`RunOptions::with_command_signal`] on Unix, user-defined control codes (128 to 255, used as the
+    /// identifier) from the Windows service manager, the console or [`ServiceHandle::send_command`]. An error
+    /// is logged, but the service keeps running. The default implementation ignores the command.
+    fn command(&mut self, command: u32) -> Result<()> {
+        tracing::warn!(
+            "Service '{}' does not handle command {command}",
+            self.name()
+        );
+        Ok(())
+    }
+}
+
+/// A request sent to the runtime while the service is running
+#[derive(Clone)]
+pub(crate) enum ControlEvent {
+    Shutdown(ShutdownReason),
+    Reload,
+    Pause,
+    Resume,
+    Command(u32),
+    Status,
+    // Answered with the current state of the service
+    #[cfg(unix)]
+    Query(Sender<control::ControlReport>),
+    AppExited,
 }
 
-#[cfg(not(windows))]
-fn start_service(app: Box<dyn ServiceApp + Send>) -> Result<()> {
-    run_interactive(app)
+/// Sends control events to the runtime, whether it waits on a blocking or an asynchronous channel
+#[derive(Clone)]
+pub(crate) enum ControlSender {
+    Blocking(Sender<ControlEvent>),
+    #[cfg(feature = "tokio")]
+    Async(tokio::sync::mpsc::UnboundedSender<ControlEvent>),
 }
 
-fn run_interactive(mut app: Box<dyn ServiceApp + Send>) -> Result<()> {
+impl ControlSender {
+    /// Returns `false` if the runtime is no longer waiting
+    pub(crate) fn send(&self, event: ControlEvent) -> bool {
+        match self {
+            ControlSender::Blocking(tx) => tx.send(event).is_ok(),
+            #[cfg(feature = "tokio")]
+            ControlSender::Async(tx) => tx.send(event).is_ok(),
+        }
+    }
+}
+
+/// Everything sending control events to a run (other than the app itself), detached when dropped
+struct ControlSources {
+    _signals: signals::SignalSubscription,
+    _console: Option<console::ConsoleSubscription>,
+    #[cfg(unix)]
+    _control: Option<control::ControlSocket>,
+}
+
+// The signal handler is installed before the app is started, so a termination signal sent as soon as the
+// app reports ready is never missed
+fn attach_control_sources(
+    name: &str,
+    tx: ControlSender,
+    options: &RunOptions,
+) -> Result<ControlSources> {
+    let signals = signals::install_handler(tx.clone(), options)?;
+    // Without a terminal, stdin is usually redirected for another purpose (or from /dev/null)
+    let console = (options.console && (options.piped_console || io::stdin().is_terminal()))
+        .then(|| console::attach(tx.clone()));
+    #[cfg(unix)]
+    let control = match options.control_socket {
+        true => {
+            let path = options
+                .control_socket_path
+                .clone()
+                .unwrap_or_else(|| control_socket_path(name));
+            Some(control::listen(&path, tx)?)
+        }
+        false => None,
+    };
+    #[cfg(not(unix))]
+    let _ = name;
+
+    Ok(ControlSources {
+        _signals: signals,
+        _console: console,
+        #[cfg(unix)]
+        _control: control,
+    })
+}
+
+/// Options for [`run_service_with`] (and `run_service_async_with`).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunOptions {
+    console: bool,
+    piped_console: bool,
+    // Only set on Unix
+    pause_signals: Option<(i32, i32)>,
+    command_signals: Vec<(i32, u32)>,
+    #[cfg(unix)]
+    control_socket: bool,
+    #[cfg(unix)]
+    control_socket_path: Option<PathBuf>,
+    health_check_interval: Option<Duration>,
+    failing_timeout: Option<Duration>,
+}
+
+impl RunOptions {
+    /// Creates run options with the default settings (the same as [`run_service`]).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads commands from stdin while running interactively (it is ignored in service mode), so the
+    /// lifecycle hooks the OS service manager would use can be tested without installing the service. Type
+    /// `help` for the list of commands. It is only used when stdin is a terminal, unless
+    /// [`with_piped_console`](Self::with_piped_console) is also set.
+    pub fn with_console(mut self, console: bool) -> Self {
+        self.console = console;
+        self
+    }
+
+    /// Also reads the console commands when stdin is not a terminal, so they can be piped in (for example, by
+    /// a test). It only has an effect along with [`with_console`](Self::with_console).
+    pub fn with_piped_console(mut self, piped_console: bool) -> Self {
+        self.piped_console = piped_console;
+        self
+    }
+
+    /// Pauses the service when the `pause` signal is received and resumes it when the `resume` signal is
+    /// received (typically `SIGUSR1` and `SIGUSR2`). No signals are used by default, as the default action of
+    /// most signals is to terminate the process. The signals can't be `SIGINT`, `SIGTERM` or `SIGHUP`, nor
+    /// one that can't be handled (such as `SIGKILL`), otherwise running the service fails.
+    #[cfg(unix)]
+    pub fn with_pause_signals(mut self, pause: i32, resume: i32) -> Self {
+        self.pause_signals = Some((pause, resume));
+        self
+    }
+
+    /// Sends `command` to the service when `signal` is received. See [`ServiceApp::command`]. It can be called
+    /// once per signal, and the same restrictions as [`with_pause_signals`](Self::with_pause_signals) apply.
+    #[cfg(unix)]
+    pub fn with_command_signal(mut self, signal: i32, command: u32) -> Self {
+        self.command_signals.push((signal, command));
+        self
+    }
+
+    /// Listens on a Unix domain socket for control requests, so tools like `uni_service_manager` can query
+    /// the live state of the service and control it. The socket is at [`control_socket_path`] unless set
+    /// with [`with_control_socket_path`](Self::with_control_socket_path), and only its owner can connect.
+    ///
+    /// The protocol is line based: each request line (`status`, `health`, `reload`, `pause`, `resume`, `stop`
+    /// or `command <number>`) is answered with a line holding `ok`, `ok <value>` or `error <message>`.
+    #[cfg(unix)]
+    pub fn with_control_socket(mut self, control_socket: bool) -> Self {
+        self.control_socket = control_socket;
+        self
+    }
+
+    /// Listens on the control socket at `path` instead of the default one. See
+    /// [`with_control_socket`](Self::with_control_socket).
+    #[cfg(unix)]
+    pub fn with_control_socket_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.control_socket = true;
+        self.control_socket_path = Some(path.into());
+        self
+    }
+
+    /// Checks [`ServiceApp::health`] every `interval` while the service is running, reporting each change.
+    /// By default, the health is only checked periodically when a
+    /// [failing timeout](Self::with_failing_timeout) is set (every 10 seconds), so an idle service is never
+    /// woken up for it. The systemd watchdog checks the health on its own schedule either way.
+    pub fn with_health_check_interval(mut self, interval: Duration) -> Self {
+        self.health_check_interval = Some(interval);
+        self
+    }
+
+    /// Stops the service once its health has been failing for longer than `timeout`, so the run returns an
+    /// error and the service manager can restart it. This enables the periodic health checks (see
+    /// [`with_health_check_interval`](Self::with_health_check_interval)). By default, a failing service keeps
+    /// running.
+    pub fn with_failing_timeout(mut self, timeout: Duration) -> Self {
+        self.failing_timeout = Some(timeout);
+        self
+    }
+}
+
+fn run_interactive(app: Box<dyn ServiceApp + Send>, options: &RunOptions) -> Result<()> {
+    let (control_tx, control_rx) = channel();
+    let _sources = attach_control_sources(
+        app.name(),
+        ControlSender::Blocking(control_tx.clone()),
+        options,
+    )?;
+    run_app(app, control_tx, control_rx, options, |_| {})
+}
+
+/// Runs the app until it is asked to shut down over `control_rx` or it exits, reporting each state change
+pub(crate) fn run_app(
+    mut app: Box<dyn ServiceApp + Send>,
+    control_tx: Sender<ControlEvent>,
+    control_rx: Receiver<ControlEvent>,
+    options: &RunOptions,
+    set_state: impl Fn(ServiceState),
+) -> Result<()> {
+    let notifies_exit = set_exit_notifier(&mut *app, control_tx);
+
     app.start()?;
-    wait_for_shutdown(&*app)?;
-    app.stop()?;
-    Ok(())
+    notify::notify_ready();
+    set_state(ServiceState::Running);
+    // Wait for termination signal or service to exit
+    let reason =
+        wait_for_shutdown_or_exit(control_rx, &mut *app, notifies_exit, options, &set_state)?;
+    notify::notify_stopping();
+    set_state(ServiceState::Stopping);
+    let name = app.name().to_string();
+    app.stop_with_reason(reason)?;
+    stop_result(&name, reason)
+}
+
+// A service stopped for failing its health checks failed, even if it stopped cleanly
+fn stop_result(name: &str, reason: ShutdownReason) -> Result<()> {
+    match reason {
+        ShutdownReason::Unhealthy => {
+            Err(format!("Service '{name}' was stopped after failing its health checks").into())
+        }
+        _ => Ok(()),
+    }
 }
 
 // NOTE: Windows operates in two possible modes: regular or services mode. UNIX variants operate just in regular mode
 /// Executes a service. If being started by the service manager, `service_mode` must be `true`.
-/// If being started interactively, `service_mode` must be `false`.
+/// If being started interactively, `service_mode` must be `false`. An error is returned if the service
+/// failed, and the process should then exit with [`exit_code`] so the service manager sees the failure.
+/// In Windows service mode, the exit code is instead reported to the service manager directly.
+/// See [`run_service_auto`] to detect the mode instead.
 pub fn run_service(app: impl ServiceApp + Send + 'static, service_mode: bool) -> Result<()> {
+    run_service_with(app, service_mode, RunOptions::default())
+}
+
+/// Executes a service like [`run_service`], with the given options.
+pub fn run_service_with(
+    app: impl ServiceApp + Send + 'static,
+    service_mode: bool,
+    options: RunOptions,
+) -> Result<()> {
     let app = Box::new(app);
 
-    if service_mode {
-        start_service(app)
-    } else {
-        run_interactive(app)
+    match service_mode {
+        #[cfg(windows)]
+        true => start_service(app, options),
+        // Outside of Windows, services run as they would interactively, except they have no console
+        #[cfg(not(windows))]
+        true => run_interactive(app, &options.with_console(false)),
+        false => run_interactive(app, &options),
     }
 }
 
-fn wait_for_shutdown(app: &dyn ServiceApp) -> Result<()> {
-    let (tx, rx) = channel();
-    ctrlc::set_handler(move || tx.send(()).expect("Could not send signal on channel."))?;
-
-    // Wait for termination signal or service to exit
-    wait_for_shutdown_or_exit(rx, app)?;
-    Ok(())
+fn set_exit_notifier(app: &mut dyn ServiceApp, control_tx: Sender<ControlEvent>) -> bool {
+    let notifier = ExitNotifier::new(move || {
+        // If the receiver is gone, the runtime is no longer waiting
+        let _ = control_tx.send(ControlEvent::AppExited);
+    });
+    app.set_exit_notifier(notifier)
 }
 
-fn wait_for_shutdown_or_exit(shutdown_rx: Receiver<()>, app: &dyn ServiceApp) -> Result<()> {
+// If `notifies_exit` is `true`, this only wakes up for control events (and health checks and watchdog
+// pings), otherwise it also polls whether the app is still running. Pausing and resuming is reported
+// through `set_state`.
+fn wait_for_shutdown_or_exit(
+    control_rx: Receiver<ControlEvent>,
+    app: &mut dyn ServiceApp,
+    notifies_exit: bool,
+    options: &RunOptions,
+    set_state: &impl Fn(ServiceState),
+) -> Result<ShutdownReason> {
+    let mut run_loop = RunLoop::new(options, notifies_exit, set_state);
+
     while app.is_running() {
-        match shutdown_rx.recv_timeout(Duration::from_millis(100)) {
-            Ok(_) => break,
+        let timeout = match run_loop.before_wait(app.name(), || app.health()) {
+            ControlFlow::Continue(timeout) => timeout,
+            ControlFlow::Break(reason) => return Ok(reason),
+        };
+        let event = match timeout {
+            Some(timeout) => control_rx.recv_timeout(timeout),
+            None => control_rx.recv().map_err(RecvTimeoutError::from),
+        };
+        let event = match event {
+            Ok(event) => event,
             Err(RecvTimeoutError::Timeout) => continue,
             Err(err) => return Err(err.into()),
+        };
+
+        match run_loop.handle_event(event, app.name(), app.can_pause(), || app.health()) {
+            Step::Call(call) => {
+                let result = match call {
+                    AppCall::Reload => app.reload(),
+                    AppCall::Pause => app.pause(),
+                    AppCall::Resume => app.resume(),
+                    AppCall::Command(command) => app.command(command),
+                };
+                run_loop.finish(app.name(), call, result);
+            }
+            Step::Continue => {}
+            Step::Stop(reason) => return Ok(reason),
         }
     }
-    Ok(())
+    Ok(ShutdownReason::AppExited)
 }
@@ -0,0 +1,314 @@
+use std::io;
+#[cfg(target_os = "linux")]
+use std::{
+    fmt::{self, Write as _},
+    os::unix::net::UnixDatagram,
+    path::PathBuf,
+};
+
+#[cfg(target_os = "linux")]
+use tracing::{
+    Event,
+    field::{Field, Visit},
+    span,
+};
+use tracing::{Level, Subscriber, level_filters::LevelFilter};
+#[cfg(target_os = "linux")]
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::{
+    Layer, layer::SubscriberExt as _, registry::LookupSpan, util::SubscriberInitExt as _,
+};
+
+use crate::Result;
+
+// Where systemd-journald listens for the native protocol
+#[cfg(target_os = "linux")]
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+// Fields that the journal gives a meaning to, or that are set for each event, so fields of the app with these
+// names are prefixed
+#[cfg(target_os = "linux")]
+const RESERVED_FIELDS: &[&str] = &[
+    "CODE_FILE",
+    "CODE_FUNC",
+    "CODE_LINE",
+    "DOCUMENTATION",
+    "ERRNO",
+    "INVOCATION_ID",
+    "MESSAGE",
+    "MESSAGE_ID",
+    "PRIORITY",
+    "SYSLOG_FACILITY",
+    "SYSLOG_IDENTIFIER",
+    "SYSLOG_PID",
+    "SYSLOG_RAW",
+    "SYSLOG_TIMESTAMP",
+    "TARGET",
+    "TID",
+];
+// Prepended to the names of the reserved fields
+#[cfg(target_os = "linux")]
+const RESERVED_FIELD_PREFIX: &str = "F_";
+
+// *** ServiceLogger ***
+
+/// Sets up `tracing` according to how the service is run. In service mode under systemd, events are sent
+/// straight to the journal using its native protocol, so they keep their level (`PRIORITY=`), the service
+/// name (`SYSLOG_IDENTIFIER=`) and their fields and those of their spans (as upper case journal fields, such
+/// as `REQUEST_ID=`, with an `F_` prefix for the names the journal uses itself, such as `F_PRIORITY=`).
+/// Otherwise (interactively, or without a journal), they are written to stderr in a human readable format.
+pub struct ServiceLogger {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    identifier: String,
+    service_mode: bool,
+    max_level: Level,
+    #[cfg(target_os = "linux")]
+    journal_socket: PathBuf,
+}
+
+impl ServiceLogger {
+    /// Creates a logger for the service named `name` (typically [`ServiceApp::name`](crate::ServiceApp::name)),
+    /// used as the journal identifier. `service_mode` should be the same as the one passed to
+    /// [`run_service`](crate::run_service) (see [`detect_service_mode`](crate::detect_service_mode)).
+    pub fn new(name: impl Into<String>, service_mode: bool) -> Self {
+        Self {
+            identifier: name.into(),
+            service_mode,
+            max_level: Level::INFO,
+            #[cfg(target_os = "linux")]
+            journal_socket: PathBuf::from(JOURNAL_SOCKET),
+        }
+    }
+
+    /// Sets the most verbose level logged by [`init`](Self::init). The default is `INFO`.
+    pub fn with_max_level(mut self, level: Level) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    /// Sends the events to the journal socket at `path` instead of the one systemd-journald listens on
+    /// (for example, a local datagram socket standing in for the journal in tests).
+    #[cfg(target_os = "linux")]
+    pub fn with_journal_socket(mut self, path: impl Into<PathBuf>) -> Self {
+        self.journal_socket = path.into();
+        self
+    }
+
+    /// Returns whether events are sent to the journal rather than to stderr. This is only the case in service
+    /// mode when the service was started by systemd (`JOURNAL_STREAM` or `INVOCATION_ID` is set) and the
+    /// journal socket exists, so a service run by another service manager on a systemd host keeps logging to
+    /// stderr.
+    pub fn uses_journal(&self) -> bool {
+        #[cfg(target_os = "linux")]
+        let journal_available = ["JOURNAL_STREAM", "INVOCATION_ID"]
+            .iter()
+            .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty()))
+            && self.journal_socket.exists();
+        #[cfg(not(target_os = "linux"))]
+        let journal_available = false;
+        self.service_mode && journal_available
+    }
+
+    /// Returns the layer to add to a `tracing` subscriber, so it can be combined with other layers and
+    /// filters. The maximum level is not applied to it.
+    pub fn layer<S>(self) -> Box<dyn Layer<S> + Send + Sync>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        #[cfg(target_os = "linux")]
+        if self.uses_journal() {
+            match JournalLayer::new(self.identifier, self.journal_socket) {
+                Ok(layer) => return layer.boxed(),
+                Err(err) => eprintln!("Could not connect to the journal: {err}. Logging to stderr"),
+            }
+        }
+
+        tracing_subscriber::fmt::layer()
+            .with_writer(io::stderr)
+            .with_target(false)
+            .boxed()
+    }
+
+    /// Installs a subscriber logging up to the maximum level with [`layer`](Self::layer) as the global
+    /// default. It returns an error if a global default was already set.
+    pub fn init(self) -> Result<()> {
+        let max_level = LevelFilter::from_level(self.max_level);
+        tracing_subscriber::registry()
+            .with(max_level)
+            .with(self.layer())
+            .try_init()?;
+        Ok(())
+    }
+}
+
+// *** JournalLayer ***
+
+/// Sends each event as a datagram of journal fields
+#[cfg(target_os = "linux")]
+struct JournalLayer {
+    identifier: String,
+    path: PathBuf,
+    socket: UnixDatagram,
+}
+
+#[cfg(target_os = "linux")]
+impl JournalLayer {
+    fn new(identifier: String, path: PathBuf) -> io::Result<Self> {
+        Ok(Self {
+            identifier,
+            path,
+            socket: UnixDatagram::unbound()?,
+        })
+    }
+
+    fn send(&self, fields: &JournalFields) -> io::Result<()> {
+        self.socket.send_to(&fields.buffer, &self.path).map(|_| ())
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<S> Layer<S> for JournalLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = JournalFields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<JournalFields>() {
+            values.record(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut fields = JournalFields::for_event();
+        fields.put("PRIORITY", priority(*metadata.level()));
+        fields.put("SYSLOG_IDENTIFIER", &self.identifier);
+        fields.put("TARGET", metadata.target());
+        if let Some(file) = metadata.file() {
+            fields.put("CODE_FILE", file);
+        }
+        if let Some(line) = metadata.line() {
+            fields.put("CODE_LINE", &line.to_string());
+        }
+
+        // From the outermost span in, then the fields of the event itself
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<JournalFields>() {
+                    fields.buffer.extend_from_slice(&span_fields.buffer);
+                }
+            }
+        }
+        event.record(&mut fields);
+
+        // The journal can't be logged to, so stderr is the next best thing (systemd captures it too)
+        if let Err(err) = self.send(&fields) {
+            eprintln!(
+                "Could not send to the journal ({err}): {} {}",
+                metadata.level(),
+                fields.message
+            );
+        }
+    }
+}
+
+// systemd uses the syslog priorities
+#[cfg(target_os = "linux")]
+fn priority(level: Level) -> &'static str {
+    match level {
+        Level::ERROR => "3",
+        Level::WARN => "4",
+        Level::INFO => "6",
+        Level::DEBUG | Level::TRACE => "7",
+    }
+}
+
+// *** JournalFields ***
+
+/// Fields encoded in the journal native protocol. The message of an event is also kept for the stderr fallback.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+struct JournalFields {
+    buffer: Vec<u8>,
+    // Only the message of the event itself is the `MESSAGE=` field, not a `message` field of a span
+    is_event: bool,
+    message: String,
+}
+
+#[cfg(target_os = "linux")]
+impl JournalFields {
+    fn for_event() -> Self {
+        Self {
+            is_event: true,
+            ..Self::default()
+        }
+    }
+
+    fn put(&mut self, name: &str, value: &str) {
+        self.buffer.extend_from_slice(name.as_bytes());
+        // A value with a newline is written as its length (64-bit little endian) followed by the raw bytes
+        if value.contains('\n') {
+            self.buffer.push(b'\n');
+            self.buffer
+                .extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            self.buffer.push(b'=');
+        }
+        self.buffer.extend_from_slice(value.as_bytes());
+        self.buffer.push(b'\n');
+    }
+
+    fn put_field(&mut self, field: &Field, value: &str) {
+        if self.is_event && field.name() == "message" {
+            self.message = value.to_string();
+            self.put("MESSAGE", value);
+        } else if let Some(name) = field_name(field.name()) {
+            self.put(&name, value);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Visit for JournalFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.put_field(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        let mut buffer = String::new();
+        let _ = write!(buffer, "{value:?}");
+        self.put_field(field, &buffer);
+    }
+}
+
+// Journal field names may only hold upper case letters, digits and underscores, and must start with a letter
+// (a leading underscore is reserved for fields set by the journal itself)
+#[cfg(target_os = "linux")]
+fn field_name(name: &str) -> Option<String> {
+    let name = name.trim_start_matches(|c: char| !c.is_ascii_alphabetic());
+    let name: String = name
+        .chars()
+        .take(64)
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+
+    match name.as_str() {
+        "" => None,
+        name if RESERVED_FIELDS.contains(&name) => Some(format!("{RESERVED_FIELD_PREFIX}{name}")),
+        _ => Some(name),
+    }
+}
@@ -0,0 +1,81 @@
+use std::io::{IsTerminal as _, stderr, stdin, stdout};
+
+#[cfg(windows)]
+use crate::start_service_or_interactive;
+use crate::{Result, RunOptions, ServiceApp, run_interactive};
+
+/// Returns whether the process appears to have been started by the OS service manager, so it can be run in
+/// service mode without the caller having to say so. On Unix, this is the case if it was started by systemd
+/// (`INVOCATION_ID` or `NOTIFY_SOCKET` is set, or the parent process is PID 1 or `systemd`) or launchd
+/// (`XPC_SERVICE_NAME` is set to a job label). On all platforms, a process with no terminal attached to
+/// stdin, stdout or stderr is also assumed to be a service.
+///
+/// This is a heuristic: pass an explicit `service_mode` to [`run_service`](crate::run_service) when it
+/// guesses wrong (for example, when an interactive run has all of its output redirected).
+pub fn detect_service_mode() -> bool {
+    let service_mode = started_by_service_manager() || !has_terminal();
+    tracing::debug!("Detected service mode: {service_mode}");
+    service_mode
+}
+
+/// Executes a service, detecting whether it was started by the service manager with
+/// [`detect_service_mode`]. On Windows, if the service manager can't be connected to, the service is
+/// run interactively instead. Use [`run_service`](crate::run_service) to choose the mode explicitly.
+pub fn run_service_auto(app: impl ServiceApp + Send + 'static) -> Result<()> {
+    let app = Box::new(app);
+
+    // Outside of Windows, services run as they would interactively (the console is off by default)
+    #[cfg(windows)]
+    if detect_service_mode() {
+        return start_service_or_interactive(app, RunOptions::default());
+    }
+    run_interactive(app, &RunOptions::default())
+}
+
+fn has_terminal() -> bool {
+    stdin().is_terminal() || stdout().is_terminal() || stderr().is_terminal()
+}
+
+#[cfg(unix)]
+fn env_is_set(name: &str) -> bool {
+    std::env::var_os(name).is_some_and(|value| !value.is_empty())
+}
+
+#[cfg(unix)]
+fn started_by_service_manager() -> bool {
+    env_is_set("INVOCATION_ID")
+        || env_is_set("NOTIFY_SOCKET")
+        || is_launchd_job()
+        || parent_is_init()
+}
+
+#[cfg(windows)]
+fn started_by_service_manager() -> bool {
+    // The service manager can only be detected by connecting to it (see `run_service_auto`)
+    false
+}
+
+// Processes started from a terminal on macOS also get `XPC_SERVICE_NAME`, but set to "0" or an app's label
+#[cfg(unix)]
+fn is_launchd_job() -> bool {
+    std::env::var("XPC_SERVICE_NAME")
+        .is_ok_and(|name| !name.is_empty() && name != "0" && !name.starts_with("application."))
+}
+
+#[cfg(unix)]
+fn parent_is_init() -> bool {
+    // SAFETY: `getppid` is always successful and has no preconditions
+    let ppid = unsafe { libc::getppid() };
+    ppid == 1 || parent_is_systemd(ppid)
+}
+
+// Covers the systemd user manager, and system managers not running as PID 1 (for example, in a container)
+#[cfg(target_os = "linux")]
+fn parent_is_systemd(ppid: libc::pid_t) -> bool {
+    std::fs::read_to_string(format!("/proc/{ppid}/comm")).is_ok_and(|comm| comm.trim() == "systemd")
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn parent_is_systemd(_ppid: libc::pid_t) -> bool {
+    false
+}
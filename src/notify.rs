@@ -0,0 +1,153 @@
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt as _;
+#[cfg(unix)]
+use std::os::unix::{
+    ffi::OsStrExt as _,
+    net::{SocketAddr, UnixDatagram},
+};
+use std::{
+    env, process,
+    time::{Duration, Instant},
+};
+
+use crate::Result;
+
+#[cfg(unix)]
+const NOTIFY_SOCKET_ENV: &str = "NOTIFY_SOCKET";
+const WATCHDOG_USEC_ENV: &str = "WATCHDOG_USEC";
+const WATCHDOG_PID_ENV: &str = "WATCHDOG_PID";
+
+// *** Notify ***
+
+/// Sends a free-form status message to the service manager (the `STATUS=` field of the systemd
+/// `sd_notify` protocol). This does nothing if the process was not started by systemd with
+/// `NOTIFY_SOCKET` set, so it is always safe to call. The status must be a single line.
+pub fn notify_status(status: &str) -> Result<()> {
+    if status.contains('\n') {
+        return Err("Status message cannot contain a newline".into());
+    }
+    notify(&format!("STATUS={status}"))
+}
+
+pub(crate) fn notify_ready() {
+    if let Err(err) = notify("READY=1") {
+        tracing::warn!("Could not notify the service manager that we are ready: {err}");
+    }
+}
+
+pub(crate) fn notify_reloading() {
+    if let Err(err) = notify(&format!("RELOADING=1\nMONOTONIC_USEC={}", monotonic_usec())) {
+        tracing::warn!("Could not notify the service manager that we are reloading: {err}");
+    }
+}
+
+pub(crate) fn notify_stopping() {
+    if let Err(err) = notify("STOPPING=1") {
+        tracing::warn!("Could not notify the service manager that we are stopping: {err}");
+    }
+}
+
+#[cfg(unix)]
+fn notify(state: &str) -> Result<()> {
+    let Some(path) = env::var_os(NOTIFY_SOCKET_ENV) else {
+        return Ok(());
+    };
+
+    let addr = match path.as_bytes() {
+        [] => return Err(format!("{NOTIFY_SOCKET_ENV} is set but empty").into()),
+        // Linux abstract namespace socket
+        #[cfg(target_os = "linux")]
+        [b'@', name @ ..] => SocketAddr::from_abstract_name(name)?,
+        _ => SocketAddr::from_pathname(&path)?,
+    };
+
+    tracing::debug!("Sending '{state}' to the service manager");
+    let socket = UnixDatagram::unbound()?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn notify(_state: &str) -> Result<()> {
+    // The notify protocol is only used by systemd
+    Ok(())
+}
+
+// systemd requires the reload timestamp to be taken from `CLOCK_MONOTONIC`
+#[cfg(unix)]
+fn monotonic_usec() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is a valid timespec to write to
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
+#[cfg(not(unix))]
+fn monotonic_usec() -> u64 {
+    0
+}
+
+// *** Watchdog ***
+
+/// Keeps the systemd watchdog fed for as long as the app reports itself healthy
+pub(crate) struct Watchdog {
+    interval: Duration,
+    next_ping: Instant,
+    healthy: bool,
+}
+
+impl Watchdog {
+    /// Returns a watchdog if systemd has enabled one for this process
+    pub(crate) fn from_env() -> Option<Self> {
+        let usec = env::var(WATCHDOG_USEC_ENV).ok()?.parse::<u64>().ok()?;
+        if usec == 0 {
+            return None;
+        }
+
+        // If the PID is given, the watchdog is meant for a specific process (maybe not us)
+        if let Ok(pid) = env::var(WATCHDOG_PID_ENV)
+            && pid.parse::<u32>().ok()? != process::id()
+        {
+            return None;
+        }
+
+        // systemd recommends pinging at half the interval
+        let interval = Duration::from_micros(usec) / 2;
+        tracing::debug!("Watchdog enabled. Pinging every {interval:?}");
+        Some(Self {
+            interval,
+            next_ping: Instant::now(),
+            healthy: true,
+        })
+    }
+
+    /// The time remaining until the next ping is due
+    pub(crate) fn time_until_ping(&self) -> Duration {
+        self.next_ping.saturating_duration_since(Instant::now())
+    }
+
+    /// Pings the watchdog if a ping is due and the app is healthy. An unhealthy app is not
+    /// pinged so that systemd can restart it once the watchdog times out.
+    pub(crate) fn ping_if_due(&mut self, name: &str, is_healthy: impl FnOnce() -> bool) {
+        if Instant::now() < self.next_ping {
+            return;
+        }
+        self.next_ping = Instant::now() + self.interval;
+
+        if is_healthy() {
+            if !self.healthy {
+                tracing::info!("Service '{name}' is healthy again");
+                self.healthy = true;
+            }
+            if let Err(err) = notify("WATCHDOG=1") {
+                tracing::warn!("Could not ping the watchdog: {err}");
+            }
+        } else if self.healthy {
+            tracing::warn!("Service '{name}' is unhealthy. Withholding watchdog pings.");
+            self.healthy = false;
+        }
+    }
+}
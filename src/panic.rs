@@ -0,0 +1,233 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    error::Error,
+    fmt, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::Once,
+    time::{SystemTime, UNIX_EPOCH},
+};
+#[cfg(feature = "tokio")]
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::Result;
+
+/// The exit code used when a service function panics (the same code Rust uses for a panicking `main`).
+/// See [`exit_code`](crate::exit_code).
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    // Set while a service function runs on this thread, so the hook only captures its panics
+    static CAPTURING: Cell<bool> = const { Cell::new(false) };
+    static CAPTURED: RefCell<Option<CapturedPanic>> = const { RefCell::new(None) };
+}
+
+// What is only available from within the panic hook
+struct CapturedPanic {
+    location: Option<String>,
+    backtrace: Backtrace,
+}
+
+// *** PanicError ***
+
+/// The error returned when a service function panics. The panic is also logged, and if a crash report
+/// directory was configured, a crash report is written there.
+#[derive(Debug)]
+pub struct PanicError {
+    service: String,
+    message: String,
+    location: Option<String>,
+    crash_report: Option<PathBuf>,
+}
+
+impl PanicError {
+    fn new(service: &str, payload: &(dyn Any + Send), crash_report_dir: Option<&Path>) -> Self {
+        let captured = CAPTURED.take();
+        let mut err = Self {
+            service: service.to_string(),
+            message: panic_message(payload).to_string(),
+            location: captured
+                .as_ref()
+                .and_then(|captured| captured.location.clone()),
+            crash_report: None,
+        };
+        tracing::error!("{err}");
+
+        if let Some(dir) = crash_report_dir {
+            let backtrace = captured.map(|captured| captured.backtrace);
+            match err.write_crash_report(dir, backtrace.as_ref()) {
+                Ok(path) => {
+                    tracing::error!("Crash report written to '{}'", path.display());
+                    err.crash_report = Some(path);
+                }
+                Err(write_err) => tracing::error!(
+                    "Could not write crash report to '{}': {write_err}",
+                    dir.display()
+                ),
+            }
+        }
+        err
+    }
+
+    /// Returns the name of the service that panicked.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Returns the panic message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the source location of the panic (`file:line:column`), if known.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Returns the path of the crash report, if one was written.
+    pub fn crash_report(&self) -> Option<&Path> {
+        self.crash_report.as_deref()
+    }
+
+    fn write_crash_report(&self, dir: &Path, backtrace: Option<&Backtrace>) -> Result<PathBuf> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let pid = process::id();
+
+        let mut report = format!(
+            "service: {}\npid: {pid}\ntime: {time}\nmessage: {}\nlocation: {}\n",
+            self.service,
+            self.message,
+            self.location.as_deref().unwrap_or("unknown"),
+        );
+        if let Some(backtrace) = backtrace {
+            report.push_str(&format!("backtrace:\n{backtrace}\n"));
+        }
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!(
+            "{}-{pid}-{time}.crash",
+            file_name_safe(&self.service)
+        ));
+        fs::write(&path, report)?;
+        Ok(path)
+    }
+}
+
+impl fmt::Display for PanicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(
+                f,
+                "Service '{}' panicked at {location}: {}",
+                self.service, self.message
+            ),
+            None => write!(f, "Service '{}' panicked: {}", self.service, self.message),
+        }
+    }
+}
+
+impl Error for PanicError {}
+
+// Service names are free form, so anything that could leave `dir` or be awkward in a file name is replaced
+fn file_name_safe(name: &str) -> String {
+    match name {
+        "" => "service".to_string(),
+        name => name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect(),
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .unwrap_or("Unknown panic"),
+    }
+}
+
+// The hook is chained to the existing one, so panics are still printed as usual
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CAPTURING.get() {
+                CAPTURED.set(Some(CapturedPanic {
+                    location: info.location().map(ToString::to_string),
+                    backtrace: Backtrace::force_capture(),
+                }));
+            }
+            prev_hook(info);
+        }));
+    });
+}
+
+// *** Catching ***
+
+/// Runs a service function, turning a panic into a [`PanicError`]
+pub(crate) fn catch_panic<T>(
+    service: &str,
+    crash_report_dir: Option<&Path>,
+    f: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    install_hook();
+
+    let was_capturing = CAPTURING.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CAPTURING.set(was_capturing);
+
+    result
+        .unwrap_or_else(|payload| Err(PanicError::new(service, &*payload, crash_report_dir).into()))
+}
+
+/// Polls a service future, turning a panic into a [`PanicError`]. The future may be polled from any
+/// thread, so capturing is enabled for the duration of each poll.
+#[cfg(feature = "tokio")]
+pub(crate) struct CatchPanic<Fut> {
+    service: String,
+    crash_report_dir: Option<PathBuf>,
+    future: Pin<Box<Fut>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<Fut> CatchPanic<Fut> {
+    pub(crate) fn new(service: String, crash_report_dir: Option<PathBuf>, future: Fut) -> Self {
+        Self {
+            service,
+            crash_report_dir,
+            future: Box::pin(future),
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T, Fut: Future<Output = Result<T>>> Future for CatchPanic<Fut> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let future = &mut this.future;
+
+        match catch_panic(&this.service, this.crash_report_dir.as_deref(), || {
+            Ok(future.as_mut().poll(cx))
+        }) {
+            Ok(poll) => poll,
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt as _, OpenOptionsExt as _};
+use std::{
+    error::Error,
+    fmt,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::{self, Read as _, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::Result;
+
+/// The error returned when a PID file is locked by another process
+#[derive(Debug)]
+pub(crate) struct LockedError {
+    path: PathBuf,
+    pid: Option<u32>,
+}
+
+impl fmt::Display for LockedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' is locked by another process", self.path.display())?;
+        match self.pid {
+            Some(pid) => write!(f, " (PID {pid})"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error for LockedError {}
+
+/// A file holding the PID of this process, locked for as long as it exists so a second instance can't
+/// claim it. It is removed when dropped. As it may live in a directory shared with other users, on Unix a
+/// symbolic link is never followed and only a regular file owned by the current user is written to.
+pub(crate) struct PidFile {
+    path: PathBuf,
+    file: File,
+}
+
+impl PidFile {
+    pub(crate) fn create(path: &Path) -> Result<Self> {
+        let mut file = loop {
+            let mut file = match open_options()
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+            {
+                Ok(file) => file,
+                // Likely created by another user, which may still hold it
+                Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                    return Err(permission_denied(path, err));
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => return Err(locked_error(path, &mut file).into()),
+                Err(TryLockError::Error(err)) => return Err(err.into()),
+            }
+            // The previous owner may have removed the file between us opening and locking it, in which case
+            // the lock is on a file no one else can see
+            #[cfg(unix)]
+            if !is_same_file(path, &file)? {
+                continue;
+            }
+            break file;
+        };
+        #[cfg(unix)]
+        check_owner(path, &file)?;
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{}", process::id())?;
+        file.flush()?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // Removed while still locked. A process that opened it before then finds it was removed once it gets
+        // the lock, and creates a new one
+        if let Err(err) = fs::remove_file(&self.path) {
+            tracing::warn!("Could not remove '{}': {err}", self.path.display());
+        }
+        let _ = self.file.unlock();
+    }
+}
+
+fn open_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    options.custom_flags(libc::O_NOFOLLOW);
+    options
+}
+
+// Makes sure another user didn't plant the file (for example, as a hard link to a file of ours)
+#[cfg(unix)]
+fn check_owner(path: &Path, file: &File) -> Result<()> {
+    let metadata = file.metadata()?;
+    // SAFETY: `geteuid` is always successful and has no preconditions
+    let uid = unsafe { libc::geteuid() };
+
+    if !metadata.is_file() || metadata.nlink() != 1 {
+        Err(format!("'{}' is not a regular file", path.display()).into())
+    } else if metadata.uid() != uid {
+        Err(format!(
+            "'{}' is owned by another user (UID {})",
+            path.display(),
+            metadata.uid()
+        )
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+// The file is held by another process, or it was left behind by another user. In a directory with the
+// sticky bit set (such as `/tmp`), only its owner can remove it
+fn permission_denied(path: &Path, err: io::Error) -> Box<dyn Error + Send + Sync> {
+    let Ok(mut file) = open_options().open(path) else {
+        return err.into();
+    };
+    match file.try_lock_shared() {
+        Err(TryLockError::WouldBlock) => locked_error(path, &mut file).into(),
+        Ok(()) => format!(
+            "'{}' was left behind by another user and can't be reused. Remove it or use another path",
+            path.display()
+        )
+        .into(),
+        Err(TryLockError::Error(_)) => err.into(),
+    }
+}
+
+// Whether `path` still refers to the open file
+#[cfg(unix)]
+fn is_same_file(path: &Path, file: &File) -> Result<bool> {
+    let metadata = file.metadata()?;
+    match fs::symlink_metadata(path) {
+        Ok(path_metadata) => {
+            Ok(path_metadata.dev() == metadata.dev() && path_metadata.ino() == metadata.ino())
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn locked_error(path: &Path, file: &mut File) -> LockedError {
+    // On Windows, the lock also prevents reading the file
+    let mut pid = String::new();
+    LockedError {
+        path: path.to_path_buf(),
+        pid: file
+            .read_to_string(&mut pid)
+            .ok()
+            .and_then(|_| pid.trim().parse().ok()),
+    }
+}
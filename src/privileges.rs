@@ -0,0 +1,428 @@
+use std::{
+    ffi::{CString, c_char},
+    io, mem, ptr,
+    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    time::Duration,
+};
+
+use crate::{ExitNotifier, HealthReport, Result, ServiceApp, ShutdownReason, StartHook};
+
+// How long to wait for the app to run the start hook before warning that it might never do so
+const START_HOOK_WARNING: Duration = Duration::from_secs(10);
+// Large enough for any `passwd` or `group` entry in practice
+const LOOKUP_BUFFER_SIZE: usize = 16 * 1024;
+
+// *** Capability ***
+
+/// A Linux capability to keep after dropping privileges. See [`Credentials::with_capabilities`].
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Capability(u32);
+
+#[cfg(target_os = "linux")]
+impl Capability {
+    /// Change file ownership (`CAP_CHOWN`)
+    pub const CHOWN: Self = Self(0);
+    /// Bypass file permission checks (`CAP_DAC_OVERRIDE`)
+    pub const DAC_OVERRIDE: Self = Self(1);
+    /// Send signals to any process (`CAP_KILL`)
+    pub const KILL: Self = Self(5);
+    /// Bind to ports below 1024 (`CAP_NET_BIND_SERVICE`)
+    pub const NET_BIND_SERVICE: Self = Self(10);
+    /// Perform network administration (`CAP_NET_ADMIN`)
+    pub const NET_ADMIN: Self = Self(12);
+    /// Use raw and packet sockets (`CAP_NET_RAW`)
+    pub const NET_RAW: Self = Self(13);
+    /// Raise process priorities (`CAP_SYS_NICE`)
+    pub const SYS_NICE: Self = Self(23);
+    /// Override resource limits (`CAP_SYS_RESOURCE`)
+    pub const SYS_RESOURCE: Self = Self(24);
+
+    /// Creates a capability from its number, as defined in `linux/capability.h`.
+    pub const fn from_raw(capability: u32) -> Self {
+        Self(capability)
+    }
+
+    /// Returns the number of the capability.
+    pub const fn as_raw(&self) -> u32 {
+        self.0
+    }
+}
+
+// *** Credentials ***
+
+/// The user and groups a service switches to when dropping privileges. See [`DropPrivileges`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    user: String,
+    group: Option<String>,
+    supplementary_groups: Option<Vec<String>>,
+    #[cfg(target_os = "linux")]
+    capabilities: Vec<Capability>,
+}
+
+impl Credentials {
+    /// Creates credentials for `user`. Unless set otherwise, its primary group and the supplementary
+    /// groups it is a member of are used.
+    pub fn new(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            group: None,
+            supplementary_groups: None,
+            #[cfg(target_os = "linux")]
+            capabilities: Vec::new(),
+        }
+    }
+
+    /// Sets the group to switch to instead of the user's primary group.
+    pub fn with_group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+
+    /// Sets the supplementary groups instead of the ones the user is a member of. An empty list clears them.
+    pub fn with_supplementary_groups<S: Into<String>>(
+        mut self,
+        groups: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.supplementary_groups = Some(groups.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Keeps these capabilities after switching users (all others are lost). They are also raised as
+    /// ambient capabilities, so processes the service executes keep them too. Capabilities are per thread on
+    /// Linux, so they are only kept by the thread that calls [`apply`](Self::apply) and the threads (and
+    /// processes) it spawns afterwards.
+    #[cfg(target_os = "linux")]
+    pub fn with_capabilities(mut self, capabilities: impl IntoIterator<Item = Capability>) -> Self {
+        self.capabilities = capabilities.into_iter().collect();
+        self
+    }
+
+    /// Switches the whole process (all threads) to these credentials. The process must be running as
+    /// root. Once this succeeds, root privileges can't be regained.
+    pub fn apply(&self) -> Result<()> {
+        // SAFETY: `geteuid` is always successful and has no preconditions
+        if unsafe { libc::geteuid() } != 0 {
+            return Err(format!(
+                "Can't switch to user '{}' without running as root",
+                self.user
+            )
+            .into());
+        }
+
+        let (uid, primary_gid) = lookup_user(&self.user)?;
+        let gid = match &self.group {
+            Some(group) => lookup_group(group)?,
+            None => primary_gid,
+        };
+
+        match &self.supplementary_groups {
+            Some(groups) => {
+                let gids = groups
+                    .iter()
+                    .map(|group| lookup_group(group))
+                    .collect::<Result<Vec<_>>>()?;
+                // SAFETY: The pointer and length come from the same valid slice
+                check(unsafe { libc::setgroups(gids.len() as _, gids.as_ptr()) })?;
+            }
+            None => {
+                let user = CString::new(self.user.as_str())?;
+                // SAFETY: `user` is a valid C string
+                check(unsafe { libc::initgroups(user.as_ptr(), gid as _) })?;
+            }
+        }
+        // SAFETY: `setgid` has no preconditions
+        check(unsafe { libc::setgid(gid) })?;
+
+        #[cfg(target_os = "linux")]
+        let keep_capabilities = !self.capabilities.is_empty();
+        #[cfg(target_os = "linux")]
+        if keep_capabilities {
+            // SAFETY: `PR_SET_KEEPCAPS` takes a single integer argument
+            check(unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) })?;
+        }
+
+        // SAFETY: `setuid` has no preconditions
+        check(unsafe { libc::setuid(uid) })?;
+
+        #[cfg(target_os = "linux")]
+        if keep_capabilities {
+            set_capabilities(&self.capabilities)?;
+        }
+
+        // Make sure root can't be regained (this fails as expected once the saved user ID is dropped)
+        // SAFETY: `setuid` has no preconditions
+        if uid != 0 && unsafe { libc::setuid(0) } == 0 {
+            return Err("Root privileges could be regained after switching users".into());
+        }
+
+        tracing::info!("Switched to user '{}' (UID {uid}, GID {gid})", self.user);
+        Ok(())
+    }
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    match result {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+fn lookup_user(name: &str) -> Result<(libc::uid_t, libc::gid_t)> {
+    let c_name = CString::new(name)?;
+    let mut buffer = vec![0 as c_char; LOOKUP_BUFFER_SIZE];
+    // SAFETY: `passwd` is a plain C struct, for which all zeroes is valid
+    let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+
+    // SAFETY: All pointers are valid and the buffer length matches the buffer
+    let err = unsafe {
+        libc::getpwnam_r(
+            c_name.as_ptr(),
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    match (err, result.is_null()) {
+        (0, false) => Ok((passwd.pw_uid, passwd.pw_gid)),
+        (0, true) => Err(format!("User '{name}' not found").into()),
+        (err, _) => Err(io::Error::from_raw_os_error(err).into()),
+    }
+}
+
+fn lookup_group(name: &str) -> Result<libc::gid_t> {
+    let c_name = CString::new(name)?;
+    let mut buffer = vec![0 as c_char; LOOKUP_BUFFER_SIZE];
+    // SAFETY: `group` is a plain C struct, for which all zeroes is valid
+    let mut group: libc::group = unsafe { mem::zeroed() };
+    let mut result = ptr::null_mut();
+
+    // SAFETY: All pointers are valid and the buffer length matches the buffer
+    let err = unsafe {
+        libc::getgrnam_r(
+            c_name.as_ptr(),
+            &mut group,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    match (err, result.is_null()) {
+        (0, false) => Ok(group.gr_gid),
+        (0, true) => Err(format!("Group '{name}' not found").into()),
+        (err, _) => Err(io::Error::from_raw_os_error(err).into()),
+    }
+}
+
+// Not exposed by `libc`, see `linux/capability.h`
+#[cfg(target_os = "linux")]
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct CapabilityHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapabilityData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+// Called after `setuid` with `PR_SET_KEEPCAPS` set, so the capabilities are still permitted but no longer
+// effective
+#[cfg(target_os = "linux")]
+fn set_capabilities(capabilities: &[Capability]) -> Result<()> {
+    let header = CapabilityHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapabilityData::default(); 2];
+    for capability in capabilities {
+        let (idx, bit) = (capability.0 as usize / 32, 1 << (capability.0 % 32));
+        let data = data
+            .get_mut(idx)
+            .ok_or_else(|| format!("Unknown capability {}", capability.0))?;
+        data.effective |= bit;
+        data.permitted |= bit;
+        data.inheritable |= bit;
+    }
+
+    // SAFETY: The header and data match the layout expected for version 3
+    check(unsafe { libc::syscall(libc::SYS_capset, &header, data.as_ptr()) } as libc::c_int)?;
+    for capability in capabilities {
+        // SAFETY: `PR_CAP_AMBIENT_RAISE` takes the capability number as its only argument
+        check(unsafe {
+            libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE,
+                capability.0 as libc::c_ulong,
+                0,
+                0,
+            )
+        })?;
+    }
+    // SAFETY: `PR_SET_KEEPCAPS` takes a single integer argument
+    check(unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 0, 0, 0, 0) })?;
+    Ok(())
+}
+
+// *** DropPrivileges ***
+
+/// A service app that switches to the given [`Credentials`] once the wrapped app has bound its privileged
+/// resources (such as ports below 1024) while running as root. If the switch fails, the app is stopped and
+/// the error is returned.
+///
+/// Apps that run a [start hook](ServiceApp::set_start_hook) switch on the service thread when the service
+/// says it is done binding, so that thread keeps the [capabilities](Credentials::with_capabilities). A
+/// [`BaseService`](crate::BaseService) service function does so by calling
+/// [`ServiceContext::run_start_hook`](crate::ServiceContext::run_start_hook), and `start` waits until it
+/// has. The children of a [`ServiceGroup`](crate::ServiceGroup) each do so, and the switch happens once all
+/// of them have. Other apps switch once `start` returns.
+pub struct DropPrivileges<A> {
+    name: String,
+    app: Option<A>,
+    credentials: Credentials,
+}
+
+impl<A: ServiceApp> DropPrivileges<A> {
+    /// Wraps `app`, switching to `credentials` as it starts.
+    pub fn new(app: A, credentials: Credentials) -> Self {
+        Self {
+            name: app.name().to_string(),
+            app: Some(app),
+            credentials,
+        }
+    }
+
+    // The app is gone once it was stopped after failing to switch
+    fn app_mut(&mut self) -> Result<&mut A> {
+        self.app
+            .as_mut()
+            .ok_or_else(|| format!("Service '{}' was stopped", self.name).into())
+    }
+
+    // Waits for the app to run the start hook, warning if it takes long, as it may never call it
+    fn wait_for_hook(&self, result_receiver: &Receiver<Result<()>>) -> Result<()> {
+        let result = match result_receiver.recv_timeout(START_HOOK_WARNING) {
+            Err(RecvTimeoutError::Timeout) => {
+                tracing::warn!(
+                    "Service '{}' has not run its start hook after {START_HOOK_WARNING:?}. Still waiting...",
+                    self.name
+                );
+                result_receiver.recv().map_err(RecvTimeoutError::from)
+            }
+            result => result,
+        };
+        result.unwrap_or_else(|_| Err("Service did not run its start hook".into()))
+    }
+}
+
+impl<A: ServiceApp> ServiceApp for DropPrivileges<A> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let (result_sender, result_receiver) = channel();
+        let hook: StartHook = {
+            let credentials = self.credentials.clone();
+            Box::new(move || match credentials.apply() {
+                Ok(()) => {
+                    // If the receiver is gone, `start` already gave up on the app
+                    let _ = result_sender.send(Ok(()));
+                    Ok(())
+                }
+                // The error is returned by `start`, so the service thread only needs to stop
+                Err(err) => {
+                    let _ = result_sender.send(Err(err));
+                    Err("Privileges could not be dropped".into())
+                }
+            })
+        };
+        let runs_hook = self.app_mut()?.set_start_hook(hook);
+        self.app_mut()?.start()?;
+
+        let result = match runs_hook {
+            true => self.wait_for_hook(&result_receiver),
+            false => self.credentials.apply(),
+        };
+        if let Err(err) = result {
+            tracing::error!(
+                "Service '{}' could not switch to user '{}': {err}",
+                self.name,
+                self.credentials.user
+            );
+            if let Some(app) = self.app.take()
+                && let Err(stop_err) = Box::new(app).stop_with_reason(ShutdownReason::Programmatic)
+            {
+                tracing::error!("Service could not be stopped: {stop_err}");
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn stop(self: Box<Self>) -> Result<()> {
+        self.stop_with_reason(ShutdownReason::Programmatic)
+    }
+
+    fn stop_with_reason(mut self: Box<Self>, reason: ShutdownReason) -> Result<()> {
+        match self.app.take() {
+            Some(app) => Box::new(app).stop_with_reason(reason),
+            None => Ok(()),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        self.app.as_ref().is_some_and(|app| app.is_running())
+    }
+
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.app
+            .as_mut()
+            .is_some_and(|app| app.set_exit_notifier(notifier))
+    }
+
+    fn stop_timeout(&self) -> Option<Duration> {
+        self.app.as_ref().and_then(|app| app.stop_timeout())
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.app.as_ref().is_none_or(|app| app.is_healthy())
+    }
+
+    fn health(&self) -> HealthReport {
+        self.app
+            .as_ref()
+            .map_or_else(HealthReport::ok, |app| app.health())
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        self.app_mut()?.reload()
+    }
+
+    fn can_pause(&self) -> bool {
+        self.app.as_ref().is_some_and(|app| app.can_pause())
+    }
+
+    fn pause(&mut self) -> Result<()> {
+        self.app_mut()?.pause()
+    }
+
+    fn resume(&mut self) -> Result<()> {
+        self.app_mut()?.resume()
+    }
+
+    fn command(&mut self, command: u32) -> Result<()> {
+        self.app_mut()?.command(command)
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_RESET_WINDOW: Duration = Duration::from_secs(60);
+
+/// Controls how a failed service function is restarted in-process. After each failure (an error or a
+/// panic) the service function is restarted after a backoff that doubles with each consecutive failure,
+/// up to a maximum. Once the maximum number of retries is exceeded, the last failure is returned (and the
+/// process exits). A run that lasts at least the reset window resets the count of consecutive failures.
+///
+/// The defaults are an initial backoff of 1 second, a maximum backoff of 60 seconds, 5 retries and a
+/// reset window of 60 seconds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RestartPolicy {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    max_retries: Option<u32>,
+    reset_window: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            max_retries: Some(DEFAULT_MAX_RETRIES),
+            reset_window: DEFAULT_RESET_WINDOW,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Creates a restart policy with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the backoff before the first restart, and the maximum backoff it doubles up to.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Sets the maximum number of consecutive restarts. `None` restarts indefinitely.
+    pub fn with_max_retries(mut self, max_retries: Option<u32>) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets how long a run must last for the count of consecutive failures to be reset.
+    pub fn with_reset_window(mut self, reset_window: Duration) -> Self {
+        self.reset_window = reset_window;
+        self
+    }
+
+    /// Returns the maximum number of consecutive restarts (`None` if unlimited).
+    pub fn max_retries(&self) -> Option<u32> {
+        self.max_retries
+    }
+
+    /// Returns how long a run must last for the count of consecutive failures to be reset.
+    pub fn reset_window(&self) -> Duration {
+        self.reset_window
+    }
+
+    /// Returns the backoff before the given restart (starting at 1), or `None` if the retries are exhausted.
+    pub fn backoff(&self, restart: u32) -> Option<Duration> {
+        if self
+            .max_retries
+            .is_some_and(|max_retries| restart > max_retries)
+        {
+            return None;
+        }
+
+        let factor = 2u32.saturating_pow(restart.saturating_sub(1));
+        Some(
+            self.initial_backoff
+                .saturating_mul(factor)
+                .min(self.max_backoff),
+        )
+    }
+}
@@ -0,0 +1,183 @@
+use std::{ops::ControlFlow, time::Duration};
+
+use crate::health::HealthMonitor;
+use crate::notify::{self, Watchdog};
+use crate::{
+    ControlEvent, HealthReport, HealthState, POLL_INTERVAL, Result, RunOptions, ServiceState,
+    ShutdownReason,
+};
+
+/// A call the runtime makes on the app for a control event. Making it is the only part of handling an event
+/// that differs between the blocking and asynchronous runtimes.
+#[derive(Clone, Copy)]
+pub(crate) enum AppCall {
+    Reload,
+    Pause,
+    Resume,
+    Command(u32),
+}
+
+/// What the runtime does once an event has been handled
+pub(crate) enum Step {
+    /// Makes the call on the app, then passes its result to [`RunLoop::finish`]
+    Call(AppCall),
+    /// Waits for the next event
+    Continue,
+    /// Stops the app
+    Stop(ShutdownReason),
+}
+
+/// The state of a running app shared by the blocking and asynchronous runtimes. The runtime waits for
+/// control events and makes the calls on the app, while this runs the health checks and watchdog pings,
+/// decides what each event does and reports the resulting state through `set_state`.
+pub(crate) struct RunLoop<S> {
+    watchdog: Option<Watchdog>,
+    health: HealthMonitor,
+    // If `false`, the runtime also polls whether the app is still running
+    notifies_exit: bool,
+    paused: bool,
+    set_state: S,
+}
+
+impl<S: Fn(ServiceState)> RunLoop<S> {
+    pub(crate) fn new(options: &RunOptions, notifies_exit: bool, set_state: S) -> Self {
+        Self {
+            watchdog: Watchdog::from_env(),
+            health: HealthMonitor::new(options),
+            notifies_exit,
+            paused: false,
+            set_state,
+        }
+    }
+
+    /// Runs the health checks and watchdog pings that are due. Returns how long to wait for the next event
+    /// (`None` to wait until one arrives), or the reason to stop the app.
+    pub(crate) fn before_wait(
+        &mut self,
+        name: &str,
+        health: impl Fn() -> HealthReport,
+    ) -> ControlFlow<ShutdownReason, Option<Duration>> {
+        if self.health.check_if_due(name, &health) {
+            tracing::error!("Service '{name}' has been failing for too long");
+            return ControlFlow::Break(ShutdownReason::Unhealthy);
+        }
+
+        let mut timeout = self.health.time_until_check();
+        if !self.notifies_exit {
+            timeout = Some(timeout.map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL)));
+        }
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.ping_if_due(name, || health().state() != HealthState::Failing);
+            let until_ping = watchdog.time_until_ping();
+            timeout = Some(timeout.map_or(until_ping, |timeout| timeout.min(until_ping)));
+        }
+        ControlFlow::Continue(timeout)
+    }
+
+    /// Handles a control event, returning what the runtime should do next.
+    pub(crate) fn handle_event(
+        &mut self,
+        event: ControlEvent,
+        name: &str,
+        can_pause: bool,
+        health: impl FnOnce() -> HealthReport,
+    ) -> Step {
+        match event {
+            ControlEvent::Shutdown(reason) => {
+                tracing::debug!("Shutdown requested: {reason}");
+                Step::Stop(reason)
+            }
+            ControlEvent::AppExited => Step::Stop(ShutdownReason::AppExited),
+            ControlEvent::Reload => {
+                tracing::info!("Reloading service '{name}'...");
+                notify::notify_reloading();
+                Step::Call(AppCall::Reload)
+            }
+            ControlEvent::Pause if self.paused => Step::Continue,
+            ControlEvent::Pause if !can_pause => {
+                tracing::warn!("Service '{name}' does not support pausing");
+                Step::Continue
+            }
+            ControlEvent::Pause => {
+                tracing::info!("Pausing service '{name}'...");
+                (self.set_state)(ServiceState::Pausing);
+                Step::Call(AppCall::Pause)
+            }
+            ControlEvent::Resume if !self.paused => Step::Continue,
+            ControlEvent::Resume => {
+                tracing::info!("Resuming service '{name}'...");
+                (self.set_state)(ServiceState::Resuming);
+                Step::Call(AppCall::Resume)
+            }
+            ControlEvent::Command(command) => {
+                tracing::debug!("Sending command {command} to service '{name}'");
+                Step::Call(AppCall::Command(command))
+            }
+            ControlEvent::Status => {
+                print_status(name, self.paused, health());
+                Step::Continue
+            }
+            #[cfg(unix)]
+            ControlEvent::Query(reply_tx) => {
+                // If the receiver is gone, the client gave up waiting
+                let _ = reply_tx.send(crate::control::ControlReport {
+                    paused: self.paused,
+                    health: health(),
+                });
+                Step::Continue
+            }
+        }
+    }
+
+    /// Reports the result of a call returned by [`handle_event`](Self::handle_event).
+    pub(crate) fn finish(&mut self, name: &str, call: AppCall, result: Result<()>) {
+        match (call, result) {
+            (AppCall::Reload, result) => {
+                if let Err(err) = result {
+                    tracing::error!("Service '{name}' could not be reloaded: {err}");
+                }
+                notify::notify_ready();
+            }
+            (AppCall::Pause, Ok(())) => {
+                self.paused = true;
+                (self.set_state)(ServiceState::Paused);
+                notify_paused_status(true);
+            }
+            (AppCall::Pause, Err(err)) => {
+                tracing::error!("Service '{name}' could not be paused: {err}");
+                (self.set_state)(ServiceState::Running);
+            }
+            (AppCall::Resume, Ok(())) => {
+                self.paused = false;
+                (self.set_state)(ServiceState::Running);
+                notify_paused_status(false);
+            }
+            (AppCall::Resume, Err(err)) => {
+                tracing::error!("Service '{name}' could not be resumed: {err}");
+                (self.set_state)(ServiceState::Paused);
+            }
+            (AppCall::Command(command), Err(err)) => {
+                tracing::error!("Service '{name}' could not run command {command}: {err}");
+            }
+            (AppCall::Command(_), Ok(())) => {}
+        }
+    }
+}
+
+// systemd has no paused state, so it is only shown in the status message
+fn notify_paused_status(paused: bool) {
+    let status = if paused { "Paused" } else { "Running" };
+    if let Err(err) = notify::notify_status(status) {
+        tracing::warn!("Could not send the status to the service manager: {err}");
+    }
+}
+
+// Printed rather than logged, as it is only requested from the console
+fn print_status(name: &str, paused: bool, report: HealthReport) {
+    let state = if paused { "paused" } else { "running" };
+    let health = match report.state() {
+        HealthState::Ok => String::new(),
+        _ => format!(" ({report})"),
+    };
+    println!("Service '{name}' is {state}{health}");
+}
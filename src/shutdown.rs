@@ -0,0 +1,201 @@
+use std::{
+    fmt,
+    future::Future,
+    mem,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard, Weak},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+// *** ShutdownReason ***
+
+/// The reason a service is being shut down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ShutdownReason {
+    /// Interrupted from the console (`SIGINT` on Unix, Ctrl-C or a similar console event on Windows)
+    Interrupt,
+    /// Asked to terminate (`SIGTERM` on Unix). This is how systemd and launchd stop a service.
+    Terminate,
+    /// Stopped by the Windows service control manager
+    ServiceControlStop,
+    /// The system is shutting down (only reported by the Windows service control manager)
+    SystemShutdown,
+    /// The app stopped running on its own
+    AppExited,
+    /// Stopped via the API instead of by the OS or service manager
+    Programmatic,
+    /// Stopped by the runtime after failing its health checks for too long (see
+    /// [`RunOptions::with_failing_timeout`](crate::RunOptions::with_failing_timeout))
+    Unhealthy,
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShutdownReason::Interrupt => "interrupted",
+            ShutdownReason::Terminate => "terminated",
+            ShutdownReason::ServiceControlStop => "stopped by the service manager",
+            ShutdownReason::SystemShutdown => "system shutdown",
+            ShutdownReason::AppExited => "app exited",
+            ShutdownReason::Programmatic => "stopped programmatically",
+            ShutdownReason::Unhealthy => "failing health checks",
+        })
+    }
+}
+
+// *** ShutdownToken ***
+
+/// A cloneable token that is cancelled when the service should shut down. All clones share the same
+/// state, so the token can be handed to any number of threads and tasks. It supports blocking waits
+/// ([`wait`](Self::wait)), async waits ([`cancelled`](Self::cancelled)) and non-blocking checks
+/// ([`is_cancelled`](Self::is_cancelled)). It is runtime agnostic and does not require `tokio`.
+#[derive(Clone, Default)]
+pub struct ShutdownToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    reason: Option<ShutdownReason>,
+    wakers: Vec<Waker>,
+    children: Vec<Weak<Inner>>,
+}
+
+impl ShutdownToken {
+    /// Creates a new token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the token, its clones and all of its child tokens, waking anyone waiting on them. The
+    /// reason is [`ShutdownReason::Programmatic`]. Cancelling an already cancelled token does nothing.
+    pub fn cancel(&self) {
+        self.cancel_with_reason(ShutdownReason::Programmatic);
+    }
+
+    /// Same as [`cancel`](Self::cancel), but with the given reason. Child tokens inherit the reason.
+    pub fn cancel_with_reason(&self, reason: ShutdownReason) {
+        Self::cancel_inner(&self.inner, reason);
+    }
+
+    fn cancel_inner(inner: &Inner, reason: ShutdownReason) {
+        let (wakers, children) = {
+            let mut state = lock(&inner.state);
+            if state.reason.is_some() {
+                return;
+            }
+            state.reason = Some(reason);
+            (mem::take(&mut state.wakers), mem::take(&mut state.children))
+        };
+
+        inner.cond.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+        for child in children.iter().filter_map(Weak::upgrade) {
+            Self::cancel_inner(&child, reason);
+        }
+    }
+
+    /// Returns `true` if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        lock(&self.inner.state).reason.is_some()
+    }
+
+    /// Returns the reason the token was cancelled, or `None` if it hasn't been cancelled.
+    pub fn reason(&self) -> Option<ShutdownReason> {
+        lock(&self.inner.state).reason
+    }
+
+    /// Blocks the current thread until the token is cancelled.
+    pub fn wait(&self) {
+        let mut state = lock(&self.inner.state);
+        while state.reason.is_none() {
+            state = self
+                .inner
+                .cond
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Blocks the current thread until the token is cancelled or `timeout` elapses. Returns `true` if
+    /// the token was cancelled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = lock(&self.inner.state);
+
+        while state.reason.is_none() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            state = self
+                .inner
+                .cond
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+        true
+    }
+
+    /// Returns a future that completes when the token is cancelled. The future owns a clone of the
+    /// token, so it can be moved into a spawned task (or passed to, for example, axum's graceful shutdown).
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+
+    /// Creates a child token. It is cancelled when this token is cancelled, but cancelling the child
+    /// does not affect this token, so a subsystem can be shut down on its own.
+    pub fn child_token(&self) -> ShutdownToken {
+        let child = ShutdownToken::new();
+
+        let mut state = lock(&self.inner.state);
+        if let Some(reason) = state.reason {
+            drop(state);
+            child.cancel_with_reason(reason);
+        } else {
+            // Children that have since been dropped no longer need to be cancelled
+            state.children.retain(|child| child.strong_count() > 0);
+            state.children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+}
+
+// A panic while holding the lock can't leave the state inconsistent, so poisoning is ignored
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+// *** Cancelled ***
+
+/// A future that completes when a [`ShutdownToken`] is cancelled. See [`ShutdownToken::cancelled`].
+pub struct Cancelled {
+    token: ShutdownToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = lock(&self.token.inner.state);
+        if state.reason.is_some() {
+            return Poll::Ready(());
+        }
+
+        if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
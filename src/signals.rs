@@ -0,0 +1,189 @@
+use std::sync::{Mutex, MutexGuard};
+
+#[cfg(unix)]
+use signal_hook::{
+    consts::{FORBIDDEN, SIGHUP, SIGINT, SIGTERM},
+    iterator::{Handle, Signals},
+    low_level::emulate_default_handler,
+};
+
+use crate::{ControlEvent, ControlSender, Result, RunOptions, ShutdownReason};
+
+// The exit code of a process terminated by Ctrl-C on Windows (`STATUS_CONTROL_C_EXIT`)
+#[cfg(windows)]
+const CONTROL_C_EXIT_CODE: i32 = 0xC000013Au32 as i32;
+
+type SubscriberFn = Box<dyn Fn(ControlEvent) + Send>;
+
+// The OS signal handlers can only be installed once per process, so they are installed on first use and
+// multiplex the control events they generate to all current subscribers
+static DISPATCHER: Mutex<Dispatcher> = Mutex::new(Dispatcher {
+    installed: false,
+    #[cfg(unix)]
+    handle: None,
+    next_id: 0,
+    subscribers: Vec::new(),
+});
+
+struct Dispatcher {
+    installed: bool,
+    // Used to handle more signals once installed
+    #[cfg(unix)]
+    handle: Option<Handle>,
+    next_id: u64,
+    subscribers: Vec<Subscriber>,
+}
+
+struct Subscriber {
+    id: u64,
+    send: SubscriberFn,
+    // The signals handled on top of the standard ones, as each subscriber may use different ones. They are
+    // never set on Windows.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    extra_signals: Vec<(i32, ControlEvent)>,
+}
+
+fn dispatcher() -> MutexGuard<'static, Dispatcher> {
+    // Subscribers can't panic while the lock is held, so the state is always consistent
+    DISPATCHER.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Unsubscribes from the OS signals when dropped
+#[must_use = "The subscription ends when dropped"]
+pub(crate) struct SignalSubscription(u64);
+
+impl Drop for SignalSubscription {
+    fn drop(&mut self) {
+        dispatcher()
+            .subscribers
+            .retain(|subscriber| subscriber.id != self.0);
+    }
+}
+
+/// Subscribes to the OS signals (installing the handlers if needed), including the extra signals set in
+/// `options`. The control events they generate are sent to `tx` until the returned subscription is dropped
+pub(crate) fn install_handler(
+    tx: ControlSender,
+    options: &RunOptions,
+) -> Result<SignalSubscription> {
+    let subscriber = Box::new(move |event| {
+        // If the receiver is gone, the runtime is no longer waiting
+        tx.send(event);
+    });
+    let mut extra_signals = Vec::new();
+    if let Some((pause, resume)) = options.pause_signals {
+        extra_signals.push((pause, ControlEvent::Pause));
+        extra_signals.push((resume, ControlEvent::Resume));
+    }
+    extra_signals.extend(
+        options
+            .command_signals
+            .iter()
+            .map(|&(signal, command)| (signal, ControlEvent::Command(command))),
+    );
+    subscribe(subscriber, extra_signals)
+}
+
+fn subscribe(
+    send: SubscriberFn,
+    extra_signals: Vec<(i32, ControlEvent)>,
+) -> Result<SignalSubscription> {
+    #[cfg(unix)]
+    check_extra_signals(&extra_signals)?;
+
+    let mut dispatcher = dispatcher();
+    if !dispatcher.installed {
+        #[cfg(unix)]
+        {
+            dispatcher.handle = Some(install()?);
+        }
+        #[cfg(windows)]
+        install()?;
+        dispatcher.installed = true;
+    }
+    #[cfg(unix)]
+    if let Some(handle) = &dispatcher.handle {
+        for (signal, _) in &extra_signals {
+            handle.add_signal(*signal)?;
+        }
+    }
+
+    let id = dispatcher.next_id;
+    dispatcher.next_id += 1;
+    dispatcher.subscribers.push(Subscriber {
+        id,
+        send,
+        extra_signals,
+    });
+    Ok(SignalSubscription(id))
+}
+
+// Adding a forbidden signal panics, and the standard ones are already used
+#[cfg(unix)]
+fn check_extra_signals(extra_signals: &[(i32, ControlEvent)]) -> Result<()> {
+    for (i, (signal, _)) in extra_signals.iter().enumerate() {
+        if FORBIDDEN.contains(signal) || [SIGINT, SIGTERM, SIGHUP].contains(signal) {
+            return Err(format!("Signal {signal} can't be handled by the service").into());
+        }
+        if extra_signals[..i].iter().any(|(other, _)| other == signal) {
+            return Err(format!("Signal {signal} is used more than once").into());
+        }
+    }
+    Ok(())
+}
+
+// Sends each subscriber the event `event_fn` returns for it (if any). Returns `false` if nobody got an event.
+fn dispatch(event_fn: impl Fn(&Subscriber) -> Option<ControlEvent>) -> bool {
+    let dispatcher = dispatcher();
+    let mut dispatched = false;
+    for subscriber in &dispatcher.subscribers {
+        if let Some(event) = event_fn(subscriber) {
+            (subscriber.send)(event);
+            dispatched = true;
+        }
+    }
+    dispatched
+}
+
+#[cfg(unix)]
+fn install() -> Result<Handle> {
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    let handle = signals.handle();
+
+    std::thread::spawn(move || {
+        for signal in signals.forever() {
+            tracing::debug!("Signal received: {signal}");
+            let event_fn = |subscriber: &Subscriber| match signal {
+                SIGHUP => Some(ControlEvent::Reload),
+                SIGINT => Some(ControlEvent::Shutdown(ShutdownReason::Interrupt)),
+                SIGTERM => Some(ControlEvent::Shutdown(ShutdownReason::Terminate)),
+                // If not found, it was added for another subscriber
+                signal => subscriber
+                    .extra_signals
+                    .iter()
+                    .find(|(extra, _)| *extra == signal)
+                    .map(|(_, event)| event.clone()),
+            };
+
+            // With no service handling it, the signal does what it would have without our handler
+            if !dispatch(event_fn)
+                && let Err(err) = emulate_default_handler(signal)
+            {
+                tracing::error!("Could not handle signal {signal}: {err}");
+            }
+        }
+    });
+    Ok(handle)
+}
+
+#[cfg(windows)]
+fn install() -> Result<()> {
+    // The console events (Ctrl-C, Ctrl-Break, close, logoff and shutdown) can't be told apart here
+    ctrlc::set_handler(move || {
+        // With no service running, exit as the default handler would have
+        if !dispatch(|_| Some(ControlEvent::Shutdown(ShutdownReason::Interrupt))) {
+            std::process::exit(CONTROL_C_EXIT_CODE);
+        }
+    })?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
+use std::cell::Cell;
 use std::ffi::{OsStr, OsString};
-use std::sync::mpsc::channel;
-use std::sync::{Mutex, OnceLock};
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
 use std::time::Duration;
 
 use uni_error::SimpleError;
@@ -10,55 +11,123 @@ use windows_service::service::{
 use windows_service::service_control_handler::{ServiceControlHandlerResult, ServiceStatusHandle};
 use windows_service::{define_windows_service, service_control_handler, service_dispatcher};
 
-use crate::{Result, ServiceApp, wait_for_shutdown_or_exit};
+use crate::{
+    ControlEvent, Result, RunOptions, ServiceApp, ShutdownReason, exit_code, run_interactive,
+    set_exit_notifier, stop_result, wait_for_shutdown_or_exit,
+};
+
+// The Win32 error returned when the process was not started by the service manager
+const ERROR_FAILED_SERVICE_CONTROLLER_CONNECT: i32 = 1063;
+
+type RegisteredApp = (Box<dyn ServiceApp + Send>, RunOptions);
 
-static SERVICE_APP: OnceLock<Mutex<Option<Box<dyn ServiceApp + Send>>>> = OnceLock::new();
+static SERVICE_APP: Mutex<Option<RegisteredApp>> = Mutex::new(None);
+
+pub(crate) fn start_service(app: Box<dyn ServiceApp + Send>, options: RunOptions) -> Result<()> {
+    let name = register_app(app, options)?;
+    service_dispatcher::start(&name, ffi_service_main)?;
+    Ok(())
+}
+
+/// Like [`start_service`], but the app is run interactively if the service manager can't be connected to
+pub(crate) fn start_service_or_interactive(
+    app: Box<dyn ServiceApp + Send>,
+    options: RunOptions,
+) -> Result<()> {
+    let name = register_app(app, options)?;
+    match service_dispatcher::start(&name, ffi_service_main) {
+        Err(windows_service::Error::Winapi(err))
+            if err.raw_os_error() == Some(ERROR_FAILED_SERVICE_CONTROLLER_CONNECT) =>
+        {
+            tracing::debug!("Not started by the service manager. Running interactively...");
+            let (app, options) = SERVICE_APP
+                .lock()
+                .expect("Mutex poisoned")
+                .take()
+                .ok_or("Service app not found")?;
+            run_interactive(app, &options)
+        }
+        result => Ok(result?),
+    }
+}
 
-pub(crate) fn start_service(app: Box<dyn ServiceApp + Send>) -> Result<()> {
+// Returns the name of the app
+fn register_app(app: Box<dyn ServiceApp + Send>, options: RunOptions) -> Result<String> {
     let name = app.name().to_string();
-    if SERVICE_APP.set(Mutex::new(Some(app))).is_err() {
+    let mut service_app = SERVICE_APP.lock().expect("Mutex poisoned");
+    if service_app.is_some() {
         return Err(SimpleError::from_kind_default_context(format!(
             "Only one service can be registered, and '{name}' already is",
         ))
         .into());
     }
-    service_dispatcher::start(&name, ffi_service_main)?;
-    Ok(())
+    *service_app = Some((app, options));
+    Ok(name)
 }
 
 define_windows_service!(ffi_service_main, service_main);
 
 // *** Service Control Handler ***
 
-struct ServiceControlHandler(ServiceStatusHandle);
+struct ServiceControlHandler {
+    handle: ServiceStatusHandle,
+    exit_code: Cell<ServiceExitCode>,
+    // Whether pause requests are accepted
+    can_pause: bool,
+}
 
 impl ServiceControlHandler {
-    fn register<F>(service_name: impl AsRef<OsStr>, event_handler: F) -> Result<Self>
+    fn register<F>(
+        service_name: impl AsRef<OsStr>,
+        can_pause: bool,
+        event_handler: F,
+    ) -> Result<Self>
     where
         F: FnMut(ServiceControl) -> ServiceControlHandlerResult + 'static + Send,
     {
-        let handle = Self(service_control_handler::register(
-            service_name,
-            event_handler,
-        )?);
+        let handle = Self {
+            handle: service_control_handler::register(service_name, event_handler)?,
+            exit_code: Cell::new(ServiceExitCode::NO_ERROR),
+            can_pause,
+        };
         handle.set_status(ServiceState::StartPending)?;
         Ok(handle)
     }
 
+    // Reported to the service manager once the service is stopped
+    fn set_exit_code(&self, code: i32) {
+        self.exit_code
+            .set(ServiceExitCode::ServiceSpecific(code as u32));
+    }
+
     fn set_status(&self, current_state: ServiceState) -> Result<()> {
-        let controls_accepted = if current_state != ServiceState::Stopped {
-            ServiceControlAccept::STOP
-        } else {
-            ServiceControlAccept::empty()
+        self.set_status_with_hint(current_state, Duration::default())
+    }
+
+    // `wait_hint` is how long the service manager should expect a pending state to last
+    fn set_status_with_hint(&self, current_state: ServiceState, wait_hint: Duration) -> Result<()> {
+        let controls_accepted = match current_state {
+            ServiceState::Stopped => ServiceControlAccept::empty(),
+            _ if self.can_pause => {
+                ServiceControlAccept::STOP
+                    | ServiceControlAccept::SHUTDOWN
+                    | ServiceControlAccept::PARAM_CHANGE
+                    | ServiceControlAccept::PAUSE_CONTINUE
+            }
+            _ => {
+                ServiceControlAccept::STOP
+                    | ServiceControlAccept::SHUTDOWN
+                    | ServiceControlAccept::PARAM_CHANGE
+            }
         };
 
-        self.0.set_service_status(ServiceStatus {
+        self.handle.set_service_status(ServiceStatus {
             service_type: ServiceType::OWN_PROCESS,
             current_state,
             controls_accepted,
-            exit_code: ServiceExitCode::Win32(0),
+            exit_code: self.exit_code.get(),
             checkpoint: 0,
-            wait_hint: Duration::default(),
+            wait_hint,
             process_id: None,
         })?;
         Ok(())
@@ -84,43 +153,102 @@ fn service_main(_arguments: Vec<OsString>) {
 fn run_service() -> Result<()> {
     tracing::debug!("Service starting...");
 
-    let (shutdown_tx, shutdown_rx) = channel();
+    let (control_tx, control_rx) = channel();
+    let exit_tx = control_tx.clone();
 
     let event_handler_fn = move |event| -> ServiceControlHandlerResult {
         tracing::debug!("Service control event received: {:?}", event);
         match event {
             ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
-            ServiceControl::Stop => {
-                if let Err(_err) = shutdown_tx.send(()) {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                let reason = match event {
+                    ServiceControl::Shutdown => ShutdownReason::SystemShutdown,
+                    _ => ShutdownReason::ServiceControlStop,
+                };
+                if let Err(_err) = control_tx.send(ControlEvent::Shutdown(reason)) {
                     tracing::error!("Could not send shutdown signal");
                 }
                 ServiceControlHandlerResult::NoError
             }
+            ServiceControl::ParamChange => {
+                if let Err(_err) = control_tx.send(ControlEvent::Reload) {
+                    tracing::error!("Could not send reload signal");
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Pause | ServiceControl::Continue => {
+                let event = match event {
+                    ServiceControl::Pause => ControlEvent::Pause,
+                    _ => ControlEvent::Resume,
+                };
+                if let Err(_err) = control_tx.send(event) {
+                    tracing::error!("Could not send pause or resume signal");
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::UserEvent(code) => {
+                if let Err(_err) = control_tx.send(ControlEvent::Command(code.to_raw())) {
+                    tracing::error!("Could not send command signal");
+                }
+                ServiceControlHandlerResult::NoError
+            }
             _ => ServiceControlHandlerResult::NotImplemented,
         }
     };
 
-    let mut app = SERVICE_APP
-        .get()
-        .expect("Missing service app")
+    let (app, options) = SERVICE_APP
         .lock()
-        .expect("Mutex poisoned");
-    let mut app = app.take().ok_or("Service app not found")?;
+        .expect("Mutex poisoned")
+        .take()
+        .ok_or("Service app not found")?;
     tracing::debug!("Registering service control handler");
-    let status_handle = ServiceControlHandler::register(app.name(), event_handler_fn)?;
+    let status_handle =
+        ServiceControlHandler::register(app.name(), app.can_pause(), event_handler_fn)?;
+
+    let result = run_app(&status_handle, app, control_rx, exit_tx, &options);
+    if let Err(err) = &result {
+        status_handle.set_exit_code(exit_code(&**err));
+    }
+
+    // Drop of handle will automatically set status to Stopped (with the exit code, if any)
+    tracing::debug!("Service exiting...");
+    result
+}
+
+fn run_app(
+    status_handle: &ServiceControlHandler,
+    mut app: Box<dyn ServiceApp + Send>,
+    control_rx: Receiver<ControlEvent>,
+    exit_tx: Sender<ControlEvent>,
+    options: &RunOptions,
+) -> Result<()> {
+    let notifies_exit = set_exit_notifier(&mut *app, exit_tx);
 
     tracing::debug!("Calling app's start method");
     app.start()?;
     status_handle.set_status(ServiceState::Running)?;
 
     tracing::debug!("Waiting for shutdown signal");
-    wait_for_shutdown_or_exit(shutdown_rx, &*app)?;
+    let set_state = |state| {
+        let state = match state {
+            crate::ServiceState::Pausing => ServiceState::PausePending,
+            crate::ServiceState::Paused => ServiceState::Paused,
+            crate::ServiceState::Resuming => ServiceState::ContinuePending,
+            _ => ServiceState::Running,
+        };
+        if let Err(err) = status_handle.set_status(state) {
+            tracing::error!("Could not set status to {state:?}: {err}");
+        }
+    };
+    let reason =
+        wait_for_shutdown_or_exit(control_rx, &mut *app, notifies_exit, options, &set_state)?;
 
     tracing::debug!("Setting status to StopPending");
-    status_handle.set_status(ServiceState::StopPending)?;
-    app.stop()?;
-
-    // Drop of handle will automatically set status to Stopped
-    tracing::debug!("Service exiting...");
-    Ok(())
+    status_handle.set_status_with_hint(
+        ServiceState::StopPending,
+        app.stop_timeout().unwrap_or_default(),
+    )?;
+    let name = app.name().to_string();
+    app.stop_with_reason(reason)?;
+    stop_result(&name, reason)
 }
@@ -5,15 +5,70 @@ use std::{
     net::{SocketAddr, TcpStream},
     sync::{
         Arc, Mutex,
+        atomic::{AtomicBool, AtomicU32, Ordering},
         mpsc::{Receiver, Sender, channel},
     },
     thread::{self, JoinHandle},
     time::Duration,
 };
 
-use uni_service::{ServiceApp, run_service};
+use uni_service::{
+    BaseService, ChildExitPolicy, ChildService, ExitCodeError, HealthReport, HealthState,
+    RestartPolicy, RunOptions, ServiceApp, ServiceGroup, ShutdownReason, notify_status,
+    run_service, run_service_with,
+};
 
 const SOCKET_TIMEOUT: Duration = Duration::from_secs(3);
+// When set, the shutdown reason is sent before the usual shutdown messages
+const REPORT_REASON_ENV: &str = "TEST_BIN_REPORT_REASON";
+// When set, a service that ignores shutdown is run with this stop timeout (in milliseconds) instead
+const STOP_TIMEOUT_ENV: &str = "TEST_BIN_STOP_TIMEOUT_MS";
+// When set with the above, the stop warning (in milliseconds) instead of half the stop timeout
+const STOP_WARNING_ENV: &str = "TEST_BIN_STOP_WARNING_MS";
+// When set, a service that fails right after starting with this exit code is run instead
+const EXIT_CODE_ENV: &str = "TEST_BIN_EXIT_CODE";
+// When set, a restarting service that fails (alternately panicking and erroring) this many times is run instead
+const FAILURES_ENV: &str = "TEST_BIN_FAILURES";
+// When set, a service that panics and writes a crash report to this directory is run instead
+const CRASH_DIR_ENV: &str = "TEST_BIN_CRASH_DIR";
+// When set, a service group whose worker fails once is run instead. The value is the worker's exit policy
+// ("restart" or "stop_all")
+const GROUP_ENV: &str = "TEST_BIN_GROUP";
+// When set, a service whose health degrades and then fails until it is stopped is run instead
+const UNHEALTHY_ENV: &str = "TEST_BIN_UNHEALTHY";
+// When set, a service that becomes unhealthy on `SIGUSR1` and healthy again on `SIGUSR2` is run instead
+#[cfg(unix)]
+const TOGGLE_HEALTH_ENV: &str = "TEST_BIN_TOGGLE_HEALTH";
+// When set, a service that serves one connection on the socket named "http" passed by the service manager
+// is run instead
+#[cfg(unix)]
+const SOCKET_ACTIVATION_ENV: &str = "TEST_BIN_SOCKET_ACTIVATION";
+// When set, an asynchronous service is run instead (on Unix, it can be paused with `SIGUSR1` and resumed with
+// `SIGUSR2`, and it listens for control requests on the socket at `TEST_BIN_CONTROL_SOCKET`, if set)
+const ASYNC_ENV: &str = "TEST_BIN_ASYNC";
+// When set, commands are read from stdin
+const CONSOLE_ENV: &str = "TEST_BIN_CONSOLE";
+// When set, the service can be paused (with `SIGUSR1` and resumed with `SIGUSR2` on Unix)
+const PAUSE_SIGNALS_ENV: &str = "TEST_BIN_PAUSE_SIGNALS";
+// When set, `SIGUSR1` and `SIGUSR2` send commands 1 and 2 to the service
+#[cfg(unix)]
+const COMMAND_SIGNALS_ENV: &str = "TEST_BIN_COMMAND_SIGNALS";
+// When set, the service listens for control requests on a socket at this path
+#[cfg(unix)]
+const CONTROL_SOCKET_ENV: &str = "TEST_BIN_CONTROL_SOCKET";
+// When set, a warning is logged with `ServiceLogger` to the journal socket at this path (in service mode) and
+// the process exits without running a service
+#[cfg(target_os = "linux")]
+const JOURNAL_SOCKET_ENV: &str = "TEST_BIN_JOURNAL_SOCKET";
+// When set, the detected service mode is sent and the process exits without running a service
+const DETECT_MODE_ENV: &str = "TEST_BIN_DETECT_MODE";
+// When set, the service is run as a daemon writing its PID to this file
+#[cfg(unix)]
+const DAEMON_PID_FILE_ENV: &str = "TEST_BIN_DAEMON_PID_FILE";
+// When set, the service switches to this user (keeping `CAP_NET_BIND_SERVICE`) once it has bound a privileged
+// port. When set along with `TEST_BIN_GROUP`, a group of services does so instead
+#[cfg(target_os = "linux")]
+const DROP_USER_ENV: &str = "TEST_BIN_DROP_USER";
 
 struct TcpClient {
     socket: TcpStream,
@@ -21,13 +76,11 @@ struct TcpClient {
 
 impl TcpClient {
     fn connect(address: &str, timeout: Duration) -> io::Result<Self> {
-        let address = address
-            .parse::<SocketAddr>()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let address = address.parse::<SocketAddr>().map_err(io::Error::other)?;
         let socket = TcpStream::connect_timeout(&address, timeout)?;
         socket.set_read_timeout(Some(timeout))?;
         socket.set_write_timeout(Some(timeout))?;
-        Ok(Self { socket: socket })
+        Ok(Self { socket })
     }
 
     fn send_message(&mut self, message: &str) -> io::Result<()> {
@@ -42,6 +95,8 @@ struct TestService {
     sender: Sender<()>,
     receiver: Option<Receiver<()>>,
     client: Option<Arc<Mutex<TcpClient>>>,
+    report_reason: bool,
+    can_pause: bool,
 }
 
 impl TestService {
@@ -53,6 +108,8 @@ impl TestService {
             sender,
             receiver: Some(receiver),
             client: client.map(|c| Arc::new(Mutex::new(c))),
+            report_reason: std::env::var_os(REPORT_REASON_ENV).is_some(),
+            can_pause: std::env::var_os(PAUSE_SIGNALS_ENV).is_some(),
         }
     }
 
@@ -102,7 +159,7 @@ impl TestService {
                     Ok(_) => Self::send_message(client.as_ref(), "quitting", "Shutting down..."),
                     Err(e) => {
                         eprintln!("Error receiving message: {}", e);
-                        Err(io::Error::new(io::ErrorKind::Other, e))
+                        Err(io::Error::other(e))
                     }
                 },
                 None => Ok(()),
@@ -118,6 +175,7 @@ impl ServiceApp for TestService {
 
     fn start(&mut self) -> uni_service::Result<()> {
         Self::send_message(self.client.as_ref(), "starting", "Startup requested")?;
+        notify_status("Starting")?;
 
         self.start_thread();
 
@@ -137,12 +195,78 @@ impl ServiceApp for TestService {
         Ok(())
     }
 
+    fn stop_with_reason(self: Box<Self>, reason: ShutdownReason) -> uni_service::Result<()> {
+        if self.report_reason {
+            Self::send_message(
+                self.client.as_ref(),
+                &format!("{reason:?}"),
+                &format!("Shutdown reason: {reason}"),
+            )?;
+        }
+        self.stop()
+    }
+
     fn is_running(&self) -> bool {
         self.handle
             .as_ref()
             .map(|handle| !handle.is_finished())
             .unwrap_or(false)
     }
+
+    fn reload(&mut self) -> uni_service::Result<()> {
+        Self::send_message(self.client.as_ref(), "reloading", "Reload requested")?;
+        Ok(())
+    }
+
+    fn can_pause(&self) -> bool {
+        self.can_pause
+    }
+
+    fn pause(&mut self) -> uni_service::Result<()> {
+        Self::send_message(self.client.as_ref(), "pausing", "Pause requested")?;
+        Ok(())
+    }
+
+    fn resume(&mut self) -> uni_service::Result<()> {
+        Self::send_message(self.client.as_ref(), "resuming", "Resume requested")?;
+        Ok(())
+    }
+
+    fn command(&mut self, command: u32) -> uni_service::Result<()> {
+        let message = format!("command {command}");
+        Self::send_message(self.client.as_ref(), &message, &message)?;
+        Ok(())
+    }
+}
+
+// A group member that reports when it is started and stopped
+struct GroupMember {
+    name: &'static str,
+    client: Option<Arc<Mutex<TcpClient>>>,
+    running: bool,
+}
+
+impl ServiceApp for GroupMember {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn start(&mut self) -> uni_service::Result<()> {
+        let message = format!("start-{}", self.name);
+        TestService::send_message(self.client.as_ref(), &message, &message)?;
+        self.running = true;
+        Ok(())
+    }
+
+    fn stop(self: Box<Self>) -> uni_service::Result<()> {
+        let message = format!("stop-{}", self.name);
+        TestService::send_message(self.client.as_ref(), &message, &message)?;
+        Ok(())
+    }
+
+    fn is_running(&self) -> bool {
+        self.running
+    }
 }
 
 impl Drop for TestService {
@@ -165,16 +289,541 @@ fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
         None => None,
     };
 
+    #[cfg(target_os = "linux")]
+    if let Some(path) = std::env::var_os(JOURNAL_SOCKET_ENV) {
+        uni_service::ServiceLogger::new("test_bin", service_mode)
+            .with_journal_socket(path)
+            .init()?;
+        let _span = tracing::info_span!("test", mode = "service", message = "span").entered();
+        tracing::warn!(answer = 42, priority = "high", "Logging to the journal");
+        return Ok(());
+    }
+    if std::env::var_os(DETECT_MODE_ENV).is_some() {
+        let (sock_msg, print_msg) = match uni_service::detect_service_mode() {
+            true => ("service", "Detected service mode"),
+            false => ("regular", "Detected interactive mode"),
+        };
+        TestService::send_message(
+            client.map(|c| Arc::new(Mutex::new(c))).as_ref(),
+            sock_msg,
+            print_msg,
+        )?;
+        return Ok(());
+    }
+    #[cfg(unix)]
+    if let Ok(pid_file) = std::env::var(DAEMON_PID_FILE_ENV) {
+        return run_daemon_service(client, pid_file);
+    }
+    #[cfg(target_os = "linux")]
+    if let Ok(user) = std::env::var(DROP_USER_ENV) {
+        if std::env::var_os(GROUP_ENV).is_some() {
+            return run_unprivileged_group(service_mode, client, user);
+        }
+        return run_unprivileged_service(service_mode, client, user);
+    }
+    if let Ok(timeout) = std::env::var(STOP_TIMEOUT_ENV) {
+        let timeout = Duration::from_millis(timeout.parse()?);
+        let warning = match std::env::var(STOP_WARNING_ENV) {
+            Ok(warning) => Duration::from_millis(warning.parse()?),
+            Err(_) => timeout / 2,
+        };
+        return run_hung_service(service_mode, client, warning, timeout);
+    }
+    if let Ok(code) = std::env::var(EXIT_CODE_ENV) {
+        return run_failing_service(service_mode, client, code.parse()?);
+    }
+    if let Ok(failures) = std::env::var(FAILURES_ENV) {
+        return run_restarting_service(service_mode, client, failures.parse()?);
+    }
+    if let Ok(crash_dir) = std::env::var(CRASH_DIR_ENV) {
+        return run_panicking_service(service_mode, client, crash_dir);
+    }
+    if let Ok(exit_policy) = std::env::var(GROUP_ENV) {
+        return run_service_group(service_mode, client, &exit_policy);
+    }
+    #[cfg(unix)]
+    if std::env::var_os(SOCKET_ACTIVATION_ENV).is_some() {
+        return run_activated_service(service_mode, client);
+    }
+    if std::env::var_os(ASYNC_ENV).is_some() {
+        return run_async_service(service_mode, client);
+    }
+    if std::env::var_os(UNHEALTHY_ENV).is_some() {
+        return run_unhealthy_service(service_mode, client);
+    }
+    #[cfg(unix)]
+    if std::env::var_os(TOGGLE_HEALTH_ENV).is_some() {
+        return run_toggled_health_service(service_mode, client);
+    }
+
     let mut service = TestService::new(service_mode, client);
     service.hello()?;
 
+    let console = std::env::var_os(CONSOLE_ENV).is_some();
+    let options = RunOptions::new()
+        .with_console(console)
+        .with_piped_console(console);
+    #[cfg(unix)]
+    let options = match std::env::var_os(PAUSE_SIGNALS_ENV) {
+        Some(_) => options.with_pause_signals(libc::SIGUSR1, libc::SIGUSR2),
+        None => options,
+    };
+    #[cfg(unix)]
+    let options = match std::env::var_os(COMMAND_SIGNALS_ENV) {
+        Some(_) => options
+            .with_command_signal(libc::SIGUSR1, 1)
+            .with_command_signal(libc::SIGUSR2, 2),
+        None => options,
+    };
+    #[cfg(unix)]
+    let options = match std::env::var_os(CONTROL_SOCKET_ENV) {
+        Some(path) => options.with_control_socket_path(path),
+        None => options,
+    };
+    run_service_with(service, service_mode, options)?;
+    Ok(())
+}
+
+fn run_hung_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    warning: Duration,
+    timeout: Duration,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let service_fn = move |_shutdown, _context| -> uni_service::Result<()> {
+        TestService::send_message(
+            client.map(|c| Arc::new(Mutex::new(c))).as_ref(),
+            "running",
+            "Service is running",
+        )?;
+        // Ignore the shutdown signal
+        loop {
+            thread::park();
+        }
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, service_mode)
+        .with_stop_warning(warning)
+        .with_stop_timeout(timeout);
+
+    run_service(service, service_mode)?;
+    Ok(())
+}
+
+fn run_failing_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    code: i32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let service_fn = move |_shutdown, _context| -> uni_service::Result<()> {
+        TestService::send_message(
+            client.map(|c| Arc::new(Mutex::new(c))).as_ref(),
+            "failing",
+            "Service is failing",
+        )?;
+        Err(ExitCodeError::with_source(code, "Service failed").into())
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, service_mode);
+
+    run_service(service, service_mode)?;
+    Ok(())
+}
+
+fn run_restarting_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    failures: u32,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+    let mut runs = 0;
+
+    let service_fn = move |shutdown: Receiver<()>, _context| -> uni_service::Result<()> {
+        runs += 1;
+        if runs <= failures {
+            TestService::send_message(client.as_ref(), "failing", "Service is failing")?;
+            if runs % 2 == 1 {
+                panic!("Service run {runs} panicked");
+            }
+            return Err(format!("Service run {runs} failed").into());
+        }
+
+        TestService::send_message(client.as_ref(), "running", "Service is running")?;
+        shutdown.recv()?;
+        TestService::send_message(client.as_ref(), "quitting", "Shutting down...")?;
+        Ok(())
+    };
+    let policy =
+        RestartPolicy::new().with_backoff(Duration::from_millis(10), Duration::from_millis(50));
+    let service =
+        BaseService::new_sync("test_bin", service_fn, service_mode).with_restart_policy(policy);
+
+    run_service(service, service_mode)?;
+    Ok(())
+}
+
+fn run_panicking_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    crash_dir: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let service_fn = move |_shutdown, _context| -> uni_service::Result<()> {
+        TestService::send_message(
+            client.map(|c| Arc::new(Mutex::new(c))).as_ref(),
+            "panicking",
+            "Service is panicking",
+        )?;
+        panic!("Service panicked on purpose");
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, service_mode)
+        .with_crash_report_dir(crash_dir);
+
+    run_service(service, service_mode)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run_activated_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+
+    let service_fn = move |shutdown: Receiver<()>,
+                           mut context: uni_service::ServiceContext<Receiver<()>>|
+          -> uni_service::Result<()> {
+        let listener = context
+            .listen_fds()
+            .take_tcp_listener("http")?
+            .ok_or("No socket named 'http' was passed")?;
+        TestService::send_message(
+            client.as_ref(),
+            "listening",
+            "Listening on the passed socket",
+        )?;
+
+        let (mut stream, _) = listener.accept()?;
+        stream.write_all(b"hello")?;
+        drop(stream);
+
+        shutdown.recv()?;
+        TestService::send_message(client.as_ref(), "quitting", "Shutting down...")?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, service_mode);
+
     run_service(service, service_mode)?;
     Ok(())
 }
 
+fn run_async_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    use tokio::sync::mpsc;
+
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+    let service_client = client.clone();
+
+    let service_fn = async move |mut shutdown: mpsc::Receiver<()>,
+                                 mut context: uni_service::ServiceContext<mpsc::Receiver<()>>|
+                -> uni_service::Result<()> {
+        let client = service_client;
+        let mut reload = context.take_reload_receiver().ok_or("No reload receiver")?;
+        let mut pause = context.take_pause_receiver().ok_or("No pause receiver")?;
+        let mut resume = context.take_resume_receiver().ok_or("No resume receiver")?;
+        TestService::send_message(client.as_ref(), "running", "Service is running")?;
+
+        loop {
+            let (sock_msg, print_msg) = tokio::select! {
+                _ = shutdown.recv() => break,
+                _ = reload.recv() => ("reloading", "Reload requested"),
+                _ = pause.recv() => ("pausing", "Pause requested"),
+                _ = resume.recv() => ("resuming", "Resume requested"),
+            };
+            TestService::send_message(client.as_ref(), sock_msg, print_msg)?;
+        }
+        TestService::send_message(client.as_ref(), "quitting", "Shutting down...")?;
+        Ok(())
+    };
+    let command_fn = move |command| -> uni_service::Result<()> {
+        let message = format!("command {command}");
+        TestService::send_message(client.as_ref(), &message, &message)?;
+        Ok(())
+    };
+    let service = uni_service::AsyncBaseService::new("test_bin", service_fn, service_mode)
+        .with_pause()
+        .with_command_handler(command_fn);
+
+    let options = RunOptions::new();
+    #[cfg(unix)]
+    let options = options.with_pause_signals(libc::SIGUSR1, libc::SIGUSR2);
+    #[cfg(unix)]
+    let options = match std::env::var_os(CONTROL_SOCKET_ENV) {
+        Some(path) => options.with_control_socket_path(path),
+        None => options,
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(uni_service::run_service_async_with(
+        service,
+        service_mode,
+        options,
+    ))?;
+    Ok(())
+}
+
+fn run_unhealthy_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+
+    let service_fn = move |shutdown: Receiver<()>, _context| -> uni_service::Result<()> {
+        TestService::send_message(client.as_ref(), "running", "Service is running")?;
+        shutdown.recv()?;
+        TestService::send_message(client.as_ref(), "quitting", "Shutting down...")?;
+        Ok(())
+    };
+    // The cache is slow from the first check, and the database is down from the second one on
+    let checks = AtomicU32::new(0);
+    let health_fn = move || {
+        let report = HealthReport::ok().with_component("cache", HealthState::Degraded, "slow");
+        match checks.fetch_add(1, Ordering::Relaxed) {
+            0 => report,
+            _ => report.with_component("database", HealthState::Failing, "down"),
+        }
+    };
+    let service =
+        BaseService::new_sync("test_bin", service_fn, service_mode).with_health_report(health_fn);
+    let options = RunOptions::new()
+        .with_health_check_interval(Duration::from_millis(50))
+        .with_failing_timeout(Duration::from_millis(200));
+
+    run_service_with(service, service_mode, options)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run_toggled_health_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+    let healthy = Arc::new(AtomicBool::new(true));
+
+    let service_fn = {
+        let client = client.clone();
+        move |shutdown: Receiver<()>, _context| -> uni_service::Result<()> {
+            TestService::send_message(client.as_ref(), "running", "Service is running")?;
+            shutdown.recv()?;
+            Ok(())
+        }
+    };
+    let health_fn = {
+        let healthy = healthy.clone();
+        move || healthy.load(Ordering::Relaxed)
+    };
+    // Command 1 makes the service unhealthy and command 2 healthy again
+    let command_fn = move |command| {
+        let (sock_msg, print_msg) = match command {
+            1 => ("unhealthy", "Service is unhealthy"),
+            _ => ("healthy", "Service is healthy"),
+        };
+        healthy.store(command != 1, Ordering::Relaxed);
+        TestService::send_message(client.as_ref(), sock_msg, print_msg)?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, service_mode)
+        .with_health_check(health_fn)
+        .with_command_handler(command_fn);
+    let options = RunOptions::new()
+        .with_command_signal(libc::SIGUSR1, 1)
+        .with_command_signal(libc::SIGUSR2, 2);
+
+    run_service_with(service, service_mode, options)?;
+    Ok(())
+}
+
+fn run_service_group(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    exit_policy: &str,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+    let exit_policy = match exit_policy {
+        "restart" => ChildExitPolicy::Restart(
+            RestartPolicy::new().with_backoff(Duration::from_millis(10), Duration::from_millis(50)),
+        ),
+        _ => ChildExitPolicy::StopAll,
+    };
+
+    let member = |name| GroupMember {
+        name,
+        client: client.clone(),
+        running: false,
+    };
+    let worker_client = client.clone();
+    let mut runs = 0;
+    let worker = move || -> Box<dyn ServiceApp + Send> {
+        let client = worker_client.clone();
+        runs += 1;
+        let run = runs;
+
+        let service_fn = move |shutdown: Receiver<()>, _context| -> uni_service::Result<()> {
+            if run == 1 {
+                TestService::send_message(client.as_ref(), "failing", "Worker is failing")?;
+                return Err(ExitCodeError::with_source(42, "Worker failed").into());
+            }
+
+            TestService::send_message(client.as_ref(), "running", "Worker is running")?;
+            shutdown.recv()?;
+            TestService::send_message(client.as_ref(), "quitting", "Worker is shutting down...")?;
+            Ok(())
+        };
+        Box::new(BaseService::new_sync("worker", service_fn, service_mode))
+    };
+
+    // Added out of order, so they are started as db, api, worker
+    let group = ServiceGroup::new("test_bin")
+        .with_child(ChildService::new(member("api")).with_dependencies(["db"]))
+        .with_child(
+            ChildService::from_factory(worker)
+                .with_dependencies(["api"])
+                .with_exit_policy(exit_policy),
+        )
+        .with_service(member("db"));
+
+    run_service(group, service_mode)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn run_daemon_service(
+    client: Option<TcpClient>,
+    pid_file: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+
+    let service_fn = move |shutdown: Receiver<()>, _context| -> uni_service::Result<()> {
+        let working_dir = std::env::current_dir()?;
+        TestService::send_message(
+            client.as_ref(),
+            &format!("running in {}", working_dir.display()),
+            "Daemon is running",
+        )?;
+        shutdown.recv()?;
+        TestService::send_message(client.as_ref(), "quitting", "Shutting down...")?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, true);
+
+    uni_service::run_service_daemon(
+        service,
+        uni_service::DaemonOptions::new().with_pid_file(pid_file),
+    )?;
+    Ok(())
+}
+
+// Binds a privileged port as root, then drops privileges and reports the user it switched to and whether it
+// can still bind a privileged port
+#[cfg(target_os = "linux")]
+fn run_unprivileged_service(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    user: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    use uni_service::{Capability, Credentials, DropPrivileges};
+
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+    let service_fn = move |shutdown: Receiver<()>,
+                           mut context: uni_service::ServiceContext<Receiver<()>>|
+          -> uni_service::Result<()> {
+        let _listener = std::net::TcpListener::bind("127.0.0.1:1020")?;
+        // SAFETY: `getuid` is always successful and has no preconditions
+        let uid = unsafe { libc::getuid() };
+        TestService::send_message(client.as_ref(), &format!("bound as {uid}"), "Bound as root")?;
+
+        context.run_start_hook()?;
+        // SAFETY: As above
+        let uid = unsafe { libc::getuid() };
+        TestService::send_message(client.as_ref(), &format!("uid {uid}"), "Switched user")?;
+        // The service thread switched users, so it kept its capabilities
+        let (sock_msg, print_msg) = match std::net::TcpListener::bind("127.0.0.1:1023") {
+            Ok(_) => ("bound", "Bound a privileged port"),
+            Err(_) => ("denied", "Could not bind a privileged port"),
+        };
+        TestService::send_message(client.as_ref(), sock_msg, print_msg)?;
+
+        let _ = shutdown.recv();
+        TestService::send_message(client.as_ref(), "stopping", "Shutdown requested")?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("test_bin", service_fn, service_mode);
+    let credentials = Credentials::new(user).with_capabilities([Capability::NET_BIND_SERVICE]);
+
+    run_service(DropPrivileges::new(service, credentials), service_mode)?;
+    Ok(())
+}
+
+// A group of two services that each bind a privileged port as root before privileges are dropped. The
+// second reports the user both switched to, as seen by each of them
+#[cfg(target_os = "linux")]
+fn run_unprivileged_group(
+    service_mode: bool,
+    client: Option<TcpClient>,
+    user: String,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    use uni_service::{Credentials, DropPrivileges, ServiceContext};
+
+    let client = client.map(|c| Arc::new(Mutex::new(c)));
+    let (uid_sender, uid_receiver) = channel();
+
+    let first_fn = move |shutdown: Receiver<()>,
+                         mut context: ServiceContext<Receiver<()>>|
+          -> uni_service::Result<()> {
+        let _listener = std::net::TcpListener::bind("127.0.0.1:1021")?;
+        context.run_start_hook()?;
+        // SAFETY: `getuid` is always successful and has no preconditions
+        uid_sender.send(unsafe { libc::getuid() })?;
+        let _ = shutdown.recv();
+        Ok(())
+    };
+    let second_fn = move |shutdown: Receiver<()>,
+                          mut context: ServiceContext<Receiver<()>>|
+          -> uni_service::Result<()> {
+        let _listener = std::net::TcpListener::bind("127.0.0.1:1022")?;
+        context.run_start_hook()?;
+        // SAFETY: As above
+        let uid = unsafe { libc::getuid() };
+        let first_uid = uid_receiver.recv()?;
+        TestService::send_message(
+            client.as_ref(),
+            &format!("uids {first_uid} {uid}"),
+            "Both services switched user",
+        )?;
+
+        let _ = shutdown.recv();
+        TestService::send_message(client.as_ref(), "stopping", "Shutdown requested")?;
+        Ok(())
+    };
+    let group = ServiceGroup::new("test_bin")
+        .with_service(BaseService::new_sync("first", first_fn, service_mode))
+        .with_child(
+            ChildService::new(BaseService::new_sync("second", second_fn, service_mode))
+                .with_dependencies(["first"]),
+        );
+
+    run_service(
+        DropPrivileges::new(group, Credentials::new(user)),
+        service_mode,
+    )?;
+    Ok(())
+}
+
 fn main() {
     if let Err(e) = run() {
         eprintln!("Error: {}", e);
-        std::process::exit(1);
+        std::process::exit(uni_service::exit_code(&*e));
     }
 }
@@ -0,0 +1,18 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use uni_service::{ServiceHandle, ServiceState};
+
+const STATE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Not used by every test crate
+#[allow(dead_code)]
+pub fn wait_for_state(handle: &ServiceHandle, state: ServiceState) {
+    let deadline = Instant::now() + STATE_TIMEOUT;
+    while handle.status() != state {
+        assert!(Instant::now() < deadline, "Timed out waiting for {state}");
+        thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -1,3 +1,2 @@
-mod sockets;
-
-pub use sockets::*;
+pub mod handle;
+pub mod sockets;
@@ -1,10 +1,19 @@
 use polling::{Event, Events, Poller};
+#[cfg(unix)]
+use std::{
+    env, fs,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    process,
+};
 use std::{
     io::{self, Read as _},
     net::{TcpListener, TcpStream},
     time::Duration,
 };
 
+// Not used by every test crate
+#[allow(dead_code)]
 pub struct TcpServer {
     listener: TcpListener,
     socket: Option<TcpStream>,
@@ -13,6 +22,7 @@ pub struct TcpServer {
     socket_key: usize,
 }
 
+#[allow(dead_code)]
 impl TcpServer {
     pub fn new(address: &str) -> io::Result<Self> {
         let socket = TcpListener::bind(address)?;
@@ -32,6 +42,7 @@ impl TcpServer {
     }
 }
 
+#[allow(dead_code)]
 impl TcpServer {
     pub fn wait_for_connection(&mut self, timeout: Duration) -> io::Result<()> {
         let mut events = Events::new();
@@ -47,7 +58,7 @@ impl TcpServer {
             }
         }
 
-        Err(io::Error::new(io::ErrorKind::Other, "No event found"))
+        Err(io::Error::other("No event found"))
     }
 
     pub fn expect_message(&mut self, message: &str, timeout: Duration) -> io::Result<()> {
@@ -60,7 +71,7 @@ impl TcpServer {
                     let mut buffer = vec![0; message.len()];
                     let n = socket.read(&mut buffer)?;
                     if n == 0 {
-                        return Err(io::Error::new(io::ErrorKind::Other, "Socket closed"));
+                        return Err(io::Error::other("Socket closed"));
                     }
                     self.poller
                         .modify(socket, Event::readable(self.socket_key))?;
@@ -69,20 +80,67 @@ impl TcpServer {
                     return if message == received_message {
                         Ok(())
                     } else {
-                        Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!(
-                                "Message mismatch: expected '{}', got '{}'",
-                                message, received_message
-                            ),
-                        ))
+                        Err(io::Error::other(format!(
+                            "Message mismatch: expected '{}', got '{}'",
+                            message, received_message
+                        )))
                     };
                 }
             }
 
-            Err(io::Error::new(io::ErrorKind::Other, "No event found"))
+            Err(io::Error::other("No event found"))
+        } else {
+            Err(io::Error::other("No socket found"))
+        }
+    }
+}
+
+#[cfg(unix)]
+#[allow(dead_code)]
+pub struct NotifyServer {
+    path: PathBuf,
+    socket: UnixDatagram,
+}
+
+#[cfg(unix)]
+#[allow(dead_code)]
+impl NotifyServer {
+    pub fn new(name: &str) -> io::Result<Self> {
+        let path = env::temp_dir().join(format!("{name}_{}.sock", process::id()));
+        // Clean up after a previous run that didn't exit cleanly
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path)?;
+        Ok(Self { path, socket })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn recv_message(&mut self, timeout: Duration) -> io::Result<String> {
+        self.socket.set_read_timeout(Some(timeout))?;
+
+        let mut buffer = vec![0; 4096];
+        let n = self.socket.recv(&mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer[..n]).into_owned())
+    }
+
+    pub fn expect_message(&mut self, message: &str, timeout: Duration) -> io::Result<()> {
+        let received_message = self.recv_message(timeout)?;
+        if message == received_message {
+            Ok(())
         } else {
-            Err(io::Error::new(io::ErrorKind::Other, "No socket found"))
+            Err(io::Error::other(format!(
+                "Message mismatch: expected '{}', got '{}'",
+                message, received_message
+            )))
         }
     }
 }
+
+#[cfg(unix)]
+impl Drop for NotifyServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc::Receiver;
+use uni_service::{
+    AsyncBaseService, AsyncServiceApp, ExitCodeError, ExitNotifier, ServiceContext, ShutdownReason,
+    exit_code, run_service_async,
+};
+
+// Exits on its own as soon as it is started, recording why it was stopped
+struct ExitingApp {
+    notifier: Option<ExitNotifier>,
+    running: bool,
+    reason: Arc<Mutex<Option<ShutdownReason>>>,
+}
+
+impl AsyncServiceApp for ExitingApp {
+    fn name(&self) -> &str {
+        "exiting_app"
+    }
+
+    async fn start(&mut self) -> uni_service::Result<()> {
+        self.running = false;
+        if let Some(notifier) = self.notifier.take() {
+            notifier.notify();
+        }
+        Ok(())
+    }
+
+    async fn stop(self) -> uni_service::Result<()> {
+        Ok(())
+    }
+
+    async fn stop_with_reason(self, reason: ShutdownReason) -> uni_service::Result<()> {
+        *self.reason.lock().expect("Mutex poisoned") = Some(reason);
+        self.stop().await
+    }
+
+    fn is_running(&self) -> bool {
+        self.running
+    }
+
+    fn set_exit_notifier(&mut self, notifier: ExitNotifier) -> bool {
+        self.notifier = Some(notifier);
+        true
+    }
+}
+
+#[tokio::test]
+async fn test_async_app_exits() {
+    let reason = Arc::new(Mutex::new(None));
+    let app = ExitingApp {
+        notifier: None,
+        running: true,
+        reason: reason.clone(),
+    };
+
+    run_service_async(app, false).await.unwrap();
+    assert_eq!(
+        *reason.lock().expect("Mutex poisoned"),
+        Some(ShutdownReason::AppExited)
+    );
+}
+
+#[tokio::test]
+async fn test_async_base_service_error() {
+    let service_fn = async |_shutdown: Receiver<()>, _context: ServiceContext<Receiver<()>>| {
+        Err(ExitCodeError::with_source(7, "Service failed").into())
+    };
+    let service = AsyncBaseService::new("failing_app", service_fn, false);
+
+    let err = run_service_async(service, false).await.unwrap_err();
+    assert_eq!(exit_code(&*err), 7);
+}
@@ -1,11 +1,19 @@
 mod common;
 
-use std::{process::Command, sync::OnceLock, thread, time::Duration};
+use std::{
+    io::Write as _,
+    process::{Command, Stdio},
+    sync::OnceLock,
+    thread,
+    time::Duration,
+};
 
 use send_ctrlc::{Interruptible as _, InterruptibleCommand as _};
 use uni_service_manager::{ServiceCapabilities, ServiceSpec, ServiceStatus, UniServiceManager};
 
-use crate::common::TcpServer;
+#[cfg(unix)]
+use crate::common::sockets::NotifyServer;
+use crate::common::sockets::TcpServer;
 
 const TIMEOUT: Duration = Duration::from_secs(3);
 
@@ -47,7 +55,888 @@ fn test_service_interactive() {
     command.wait().unwrap();
 }
 
+// Uses a local datagram socket in place of systemd's notify socket
+#[cfg(unix)]
+#[test]
+fn test_service_notify() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53167";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let mut notify = NotifyServer::new("uni_service_notify").unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("NOTIFY_SOCKET", notify.path())
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    notify.expect_message("STATUS=Starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+    // Readiness must only be signalled after `start` returns
+    notify.expect_message("READY=1", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    notify.expect_message("STOPPING=1", TIMEOUT).unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+    command.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_watchdog() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53168";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let mut notify = NotifyServer::new("uni_service_watchdog").unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("NOTIFY_SOCKET", notify.path())
+        // Pings are sent every 100ms (half the interval)
+        .env("WATCHDOG_USEC", "200000")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    notify.expect_message("STATUS=Starting", TIMEOUT).unwrap();
+    notify.expect_message("READY=1", TIMEOUT).unwrap();
+    notify.expect_message("WATCHDOG=1", TIMEOUT).unwrap();
+    notify.expect_message("WATCHDOG=1", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    loop {
+        match notify.recv_message(TIMEOUT).unwrap().as_str() {
+            "WATCHDOG=1" => continue,
+            msg => {
+                assert_eq!(msg, "STOPPING=1");
+                break;
+            }
+        }
+    }
+    command.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_reload() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53169";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    // SIGHUP reloads instead of shutting down
+    send_signal(command.id(), libc::SIGHUP);
+    server.expect_message("reloading", TIMEOUT).unwrap();
+    send_signal(command.id(), libc::SIGHUP);
+    server.expect_message("reloading", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+    command.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_pause_signals() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53182";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_PAUSE_SIGNALS", "1")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    send_signal(command.id(), libc::SIGUSR1);
+    server.expect_message("pausing", TIMEOUT).unwrap();
+    send_signal(command.id(), libc::SIGUSR2);
+    server.expect_message("resuming", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_command_signals() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53183";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_COMMAND_SIGNALS", "1")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    send_signal(command.id(), libc::SIGUSR2);
+    server.expect_message("command 2", TIMEOUT).unwrap();
+    send_signal(command.id(), libc::SIGUSR1);
+    server.expect_message("command 1", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_control_socket() {
+    use uni_service_manager::{AppHealth, AppStatus, ControlClient, ServiceErrKind};
+
+    const SERVER_ADDRESS: &str = "127.0.0.1:53184";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let socket_path = std::env::temp_dir().join(format!("test_bin_{}.sock", std::process::id()));
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_CONTROL_SOCKET", &socket_path)
+        .env("TEST_BIN_PAUSE_SIGNALS", "1")
+        .spawn()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    // Only the owner can use the socket
+    let mode = std::os::unix::fs::MetadataExt::mode(&std::fs::metadata(&socket_path).unwrap());
+    assert_eq!(mode & 0o777, 0o600);
+
+    let mut client = ControlClient::connect(&socket_path).unwrap();
+    let running = AppStatus {
+        status: ServiceStatus::Running,
+        health: AppHealth::Ok,
+        health_report: "ok".to_string(),
+    };
+    assert_eq!(client.app_status().unwrap(), running);
+
+    client.reload().unwrap();
+    server.expect_message("reloading", TIMEOUT).unwrap();
+    client.send_command(9).unwrap();
+    server.expect_message("command 9", TIMEOUT).unwrap();
+    client.pause().unwrap();
+    server.expect_message("pausing", TIMEOUT).unwrap();
+    assert_eq!(client.app_status().unwrap().status, ServiceStatus::Paused);
+    client.resume().unwrap();
+    server.expect_message("resuming", TIMEOUT).unwrap();
+    assert_eq!(client.app_status().unwrap(), running);
+
+    let err = client.request("bogus").unwrap_err();
+    assert!(matches!(
+        err.kind_ref(),
+        ServiceErrKind::ControlRequestFailed(_)
+    ));
+
+    client.stop().unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+    assert!(!socket_path.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_async() {
+    use uni_service_manager::ControlClient;
+
+    const SERVER_ADDRESS: &str = "127.0.0.1:53187";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let socket_path =
+        std::env::temp_dir().join(format!("test_bin_async_{}.sock", std::process::id()));
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_ASYNC", "1")
+        .env("TEST_BIN_CONTROL_SOCKET", &socket_path)
+        .spawn()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    send_signal(command.id(), libc::SIGHUP);
+    server.expect_message("reloading", TIMEOUT).unwrap();
+    send_signal(command.id(), libc::SIGUSR1);
+    server.expect_message("pausing", TIMEOUT).unwrap();
+    send_signal(command.id(), libc::SIGUSR2);
+    server.expect_message("resuming", TIMEOUT).unwrap();
+
+    let mut client = ControlClient::connect(&socket_path).unwrap();
+    assert_eq!(client.app_status().unwrap().status, ServiceStatus::Running);
+    client.send_command(3).unwrap();
+    server.expect_message("command 3", TIMEOUT).unwrap();
+
+    client.request("stop").unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+    assert!(!socket_path.exists());
+}
+
+// Passes a listening socket as systemd would: at descriptor 3, with `LISTEN_PID` set to the PID of the
+// service (known in advance thanks to `exec`)
+#[cfg(unix)]
+#[test]
+fn test_service_socket_activation() {
+    use std::{
+        io::Read as _,
+        net::{TcpListener, TcpStream},
+        os::{fd::AsRawFd as _, unix::process::CommandExt as _},
+    };
+
+    const SERVER_ADDRESS: &str = "127.0.0.1:53186";
+    const LISTEN_FD: libc::c_int = 3;
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let fd = listener.as_raw_fd();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new("sh");
+    command
+        .args(["-c", r#"export LISTEN_PID=$$; exec "$0" "$1""#, bin_path])
+        .arg(SERVER_ADDRESS)
+        .env("LISTEN_FDS", "1")
+        .env("LISTEN_FDNAMES", "http")
+        .env("TEST_BIN_SOCKET_ACTIVATION", "1");
+    // SAFETY: Only async-signal-safe functions are called between fork and exec
+    unsafe {
+        command.pre_exec(move || {
+            // The descriptor is inherited without its close-on-exec flag
+            let res = match fd {
+                LISTEN_FD => libc::fcntl(LISTEN_FD, libc::F_SETFD, 0),
+                _ => libc::dup2(fd, LISTEN_FD),
+            };
+            match res {
+                -1 => Err(std::io::Error::last_os_error()),
+                _ => Ok(()),
+            }
+        })
+    };
+    let mut child = command.spawn().unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("listening", TIMEOUT).unwrap();
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert_eq!(response, "hello");
+
+    send_signal(child.id(), libc::SIGTERM);
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    assert!(child.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_watchdog_unhealthy() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53189";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let mut notify = NotifyServer::new("uni_service_watchdog_unhealthy").unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("NOTIFY_SOCKET", notify.path())
+        // Pings are sent every 100ms (half the interval)
+        .env("WATCHDOG_USEC", "200000")
+        .env("TEST_BIN_TOGGLE_HEALTH", "1")
+        .spawn()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+    notify.expect_message("READY=1", TIMEOUT).unwrap();
+    notify.expect_message("WATCHDOG=1", TIMEOUT).unwrap();
+
+    send_signal(command.id(), libc::SIGUSR1);
+    server.expect_message("unhealthy", TIMEOUT).unwrap();
+    // Pings sent before the service became unhealthy may still be queued
+    while notify.recv_message(Duration::from_millis(10)).is_ok() {}
+    // No ping for several intervals while unhealthy
+    assert!(notify.recv_message(Duration::from_millis(500)).is_err());
+
+    send_signal(command.id(), libc::SIGUSR2);
+    server.expect_message("healthy", TIMEOUT).unwrap();
+    notify.expect_message("WATCHDOG=1", TIMEOUT).unwrap();
+
+    send_signal(command.id(), libc::SIGTERM);
+    loop {
+        match notify.recv_message(TIMEOUT).unwrap().as_str() {
+            "WATCHDOG=1" => continue,
+            msg => {
+                assert_eq!(msg, "STOPPING=1");
+                break;
+            }
+        }
+    }
+    assert!(command.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_health() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53185";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let mut notify = NotifyServer::new("uni_service_health").unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("NOTIFY_SOCKET", notify.path())
+        .env("TEST_BIN_UNHEALTHY", "1")
+        .spawn()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+    notify.expect_message("READY=1", TIMEOUT).unwrap();
+    notify
+        .expect_message("STATUS=Degraded: cache (slow)", TIMEOUT)
+        .unwrap();
+    notify
+        .expect_message("STATUS=Failing: cache (slow), database (down)", TIMEOUT)
+        .unwrap();
+
+    // The service is stopped once it has been failing for longer than the timeout, and exits with an error
+    notify.expect_message("STOPPING=1", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    let status = command.wait().unwrap();
+    assert_eq!(status.code(), Some(uni_service::FAILURE_EXIT_CODE));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_shutdown_reason() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53170";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_REPORT_REASON", "1")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    command.interrupt().unwrap();
+    server.expect_message("Interrupt", TIMEOUT).unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+    command.wait().unwrap();
+}
+
+#[test]
+fn test_service_stop_timeout() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53171";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_STOP_TIMEOUT_MS", "200")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    // The service ignores the shutdown signal, so the process is exited once the timeout elapses
+    command.terminate().unwrap();
+    let status = command.wait().unwrap();
+    assert_eq!(status.code(), Some(uni_service::STOP_TIMEOUT_EXIT_CODE));
+}
+
+// The timeout must be honoured even if the warning is due after it
+#[test]
+fn test_service_stop_timeout_before_warning() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53188";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_STOP_TIMEOUT_MS", "200")
+        .env("TEST_BIN_STOP_WARNING_MS", "10000")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    let stopped = std::time::Instant::now();
+    command.terminate().unwrap();
+    let status = command.wait().unwrap();
+    assert_eq!(status.code(), Some(uni_service::STOP_TIMEOUT_EXIT_CODE));
+    assert!(stopped.elapsed() < TIMEOUT);
+}
+
+#[test]
+fn test_service_exit_code() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53172";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_EXIT_CODE", "42")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("failing", TIMEOUT).unwrap();
+
+    // The service fails on its own, so the process exits without being signalled
+    let status = command.wait().unwrap();
+    assert_eq!(status.code(), Some(42));
+}
+
+#[test]
+fn test_service_restart() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53173";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_FAILURES", "3")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    for _ in 0..3 {
+        server.expect_message("failing", TIMEOUT).unwrap();
+    }
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    // The restarted run gets the shutdown signal
+    command.terminate().unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[test]
+fn test_service_restart_gives_up() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53174";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        // One more than the default maximum number of retries
+        .env("TEST_BIN_FAILURES", "6")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    for _ in 0..6 {
+        server.expect_message("failing", TIMEOUT).unwrap();
+    }
+    assert_eq!(
+        command.wait().unwrap().code(),
+        Some(uni_service::FAILURE_EXIT_CODE)
+    );
+}
+
+#[test]
+fn test_service_panic() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53175";
+    init_tracing();
+
+    let crash_dir = std::env::temp_dir().join(format!("uni_service_crash_{}", std::process::id()));
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_CRASH_DIR", &crash_dir)
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("panicking", TIMEOUT).unwrap();
+    assert_eq!(
+        command.wait().unwrap().code(),
+        Some(uni_service::PANIC_EXIT_CODE)
+    );
+
+    let reports: Vec<_> = std::fs::read_dir(&crash_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(reports.len(), 1);
+    let report = std::fs::read_to_string(&reports[0]).unwrap();
+    std::fs::remove_dir_all(&crash_dir).unwrap();
+
+    assert!(report.contains("service: test_bin\n"));
+    assert!(report.contains("message: Service panicked on purpose\n"));
+    assert!(report.contains("location: test_bin"));
+    assert!(report.contains("backtrace:\n"));
+}
+
+#[test]
+fn test_service_group_restart() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53176";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_GROUP", "restart")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("start-db", TIMEOUT).unwrap();
+    server.expect_message("start-api", TIMEOUT).unwrap();
+    server.expect_message("failing", TIMEOUT).unwrap();
+    // Only the worker is restarted
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    // Stopped in reverse order
+    command.terminate().unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("stop-api", TIMEOUT).unwrap();
+    server.expect_message("stop-db", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[test]
+fn test_service_group_stop_all() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53177";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_GROUP", "stop_all")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("start-db", TIMEOUT).unwrap();
+    server.expect_message("start-api", TIMEOUT).unwrap();
+    server.expect_message("failing", TIMEOUT).unwrap();
+    // The worker's failure stops the rest of the group
+    server.expect_message("stop-api", TIMEOUT).unwrap();
+    server.expect_message("stop-db", TIMEOUT).unwrap();
+    assert_eq!(command.wait().unwrap().code(), Some(42));
+}
+
+// Uses a local datagram socket in place of the journal
+#[cfg(target_os = "linux")]
+#[test]
+fn test_service_logger() {
+    init_tracing();
+
+    let mut journal = NotifyServer::new("uni_service_journal").unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let status = Command::new(bin_path)
+        .arg("service")
+        .env("TEST_BIN_JOURNAL_SOCKET", journal.path())
+        // Set by systemd for every unit it starts
+        .env("INVOCATION_ID", "0123456789abcdef0123456789abcdef")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let message = journal.recv_message(TIMEOUT).unwrap();
+    let fields: Vec<_> = message.lines().collect();
+    for field in [
+        "PRIORITY=4",
+        "SYSLOG_IDENTIFIER=test_bin",
+        "MESSAGE=Logging to the journal",
+        "MODE=service",
+        "ANSWER=42",
+        // Fields named like the ones the journal uses are prefixed
+        "F_MESSAGE=span",
+        "F_PRIORITY=high",
+    ] {
+        assert!(fields.contains(&field), "'{field}' not in '{message}'");
+    }
+    for name in ["PRIORITY=", "MESSAGE="] {
+        let count = fields
+            .iter()
+            .filter(|field| field.starts_with(name))
+            .count();
+        assert_eq!(count, 1, "'{name}' not once in '{message}'");
+    }
+
+    // Interactively, the event is written to stderr instead
+    let output = Command::new(bin_path)
+        .env("TEST_BIN_JOURNAL_SOCKET", journal.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("WARN"), "{stderr}");
+    assert!(
+        stderr.contains("Logging to the journal answer=42"),
+        "{stderr}"
+    );
+    assert!(journal.recv_message(Duration::from_millis(100)).is_err());
+
+    // Outside of systemd, the event is also written to stderr in service mode
+    let output = Command::new(bin_path)
+        .arg("service")
+        .env("TEST_BIN_JOURNAL_SOCKET", journal.path())
+        .env_remove("INVOCATION_ID")
+        .env_remove("JOURNAL_STREAM")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Logging to the journal"), "{stderr}");
+    assert!(journal.recv_message(Duration::from_millis(100)).is_err());
+}
+
+#[test]
+fn test_detect_service_mode() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53178";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    // Set by systemd for every unit it starts
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_DETECT_MODE", "1")
+        .env("INVOCATION_ID", "0123456789abcdef0123456789abcdef")
+        .spawn()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("service", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_service_daemon() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53179";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+    let pid_file =
+        std::env::temp_dir().join(format!("uni_service_daemon_{}.pid", std::process::id()));
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_DAEMON_PID_FILE", &pid_file)
+        .spawn()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("running in /", TIMEOUT).unwrap();
+    // The original process exits once the daemon has started
+    assert!(command.wait().unwrap().success());
+
+    let pid: libc::pid_t = std::fs::read_to_string(&pid_file)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    assert_ne!(pid, command.id() as libc::pid_t);
+
+    // SAFETY: Sending a signal has no memory safety implications
+    assert_eq!(unsafe { libc::kill(pid, libc::SIGTERM) }, 0);
+    server.expect_message("quitting", TIMEOUT).unwrap();
+
+    // The PID file is removed once the daemon stops
+    let deadline = std::time::Instant::now() + TIMEOUT;
+    while pid_file.exists() {
+        assert!(std::time::Instant::now() < deadline);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_service_drop_privileges() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53180";
+    init_tracing();
+
+    // SAFETY: `geteuid` is always successful and has no preconditions
+    if unsafe { libc::geteuid() } != 0 {
+        eprintln!("Skipping test: not running as root");
+        return;
+    }
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_DROP_USER", "nobody")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    // Privileges are only dropped once the service function has bound its privileged port
+    server.expect_message("bound as 0", TIMEOUT).unwrap();
+    server.expect_message("uid 65534", TIMEOUT).unwrap();
+    // The kept capability still allows binding a privileged port
+    server.expect_message("bound", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_service_group_drop_privileges() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53190";
+    init_tracing();
+
+    // SAFETY: `geteuid` is always successful and has no preconditions
+    if unsafe { libc::geteuid() } != 0 {
+        eprintln!("Skipping test: not running as root");
+        return;
+    }
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_DROP_USER", "nobody")
+        .env("TEST_BIN_GROUP", "1")
+        .spawn_interruptible()
+        .unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    // Both children bound their privileged ports as root, then switched once both were ready
+    server.expect_message("uids 65534 65534", TIMEOUT).unwrap();
+
+    command.terminate().unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    assert!(command.wait().unwrap().success());
+}
+
+#[test]
+fn test_service_console() {
+    const SERVER_ADDRESS: &str = "127.0.0.1:53181";
+    init_tracing();
+
+    let mut server = TcpServer::new(SERVER_ADDRESS).unwrap();
+
+    let bin_path = env!("CARGO_BIN_EXE_test_bin");
+    let mut command = Command::new(bin_path)
+        .arg(SERVER_ADDRESS)
+        .env("TEST_BIN_CONSOLE", "1")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = command.stdin.take().unwrap();
+
+    server.wait_for_connection(TIMEOUT).unwrap();
+    server.expect_message("regular", TIMEOUT).unwrap();
+    server.expect_message("starting", TIMEOUT).unwrap();
+    server.expect_message("running", TIMEOUT).unwrap();
+
+    stdin.write_all(b"reload\n").unwrap();
+    server.expect_message("reloading", TIMEOUT).unwrap();
+    stdin.write_all(b"command 7\n").unwrap();
+    server.expect_message("command 7", TIMEOUT).unwrap();
+    stdin.write_all(b"status\nbogus\nstop\n").unwrap();
+    server.expect_message("stopping", TIMEOUT).unwrap();
+    server.expect_message("quitting", TIMEOUT).unwrap();
+    server.expect_message("goodbye", TIMEOUT).unwrap();
+
+    let output = command.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Service 'test_bin' is running\n"));
+    assert!(stdout.contains("Unknown command 'bogus'"));
+}
+
 #[derive(Clone, Copy)]
+#[allow(clippy::enum_variant_names)]
 enum MultiPhase {
     NotMultiPhase,
     #[allow(dead_code)]
@@ -236,6 +1125,11 @@ fn test_windows_system_service() {
     }
 }
 
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: libc::c_int) {
+    assert_eq!(unsafe { libc::kill(pid as libc::pid_t, signal) }, 0);
+}
+
 #[cfg(not(windows))]
 fn is_root() -> bool {
     unsafe { libc::getuid() == 0 }
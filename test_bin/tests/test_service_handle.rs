@@ -0,0 +1,153 @@
+mod common;
+
+use std::{
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use uni_service::{BaseService, ServiceContext, ServiceState, spawn_service};
+
+use crate::common::handle::wait_for_state;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+#[test]
+fn test_service_handle() {
+    let (events_tx, events_rx) = mpsc::channel();
+    let (reload_tx, reload_rx) = mpsc::channel();
+
+    let service_fn = move |shutdown: Receiver<()>, mut context: ServiceContext<Receiver<()>>| {
+        let reload = context.take_reload_receiver().unwrap();
+        loop {
+            if shutdown.recv_timeout(Duration::from_millis(10)).is_ok() {
+                events_tx.send("quitting")?;
+                return Ok(());
+            }
+            if reload.try_recv().is_ok() {
+                events_tx.send("reloading")?;
+            }
+        }
+    };
+    let service = BaseService::new_sync("handle_test", service_fn, false).with_reload(
+        move || {
+            reload_tx.send(())?;
+            Ok(())
+        },
+        reload_rx,
+    );
+
+    let handle = spawn_service(service).unwrap();
+    assert_eq!(handle.name(), "handle_test");
+    wait_for_state(&handle, ServiceState::Running);
+
+    handle.reload().unwrap();
+    assert_eq!(events_rx.recv_timeout(TIMEOUT).unwrap(), "reloading");
+
+    handle.stop();
+    assert_eq!(events_rx.recv_timeout(TIMEOUT).unwrap(), "quitting");
+    wait_for_state(&handle, ServiceState::Stopped);
+    assert!(handle.reload().is_err());
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_service_handle_exits() {
+    let service_fn = |_shutdown: Receiver<()>, _context| Err("Service failed".into());
+    let service = BaseService::new_sync("handle_test", service_fn, false);
+
+    let handle = spawn_service(service).unwrap();
+    wait_for_state(&handle, ServiceState::Stopped);
+    // Asking a stopped service to stop does nothing
+    handle.stop();
+    assert_eq!(handle.join().unwrap_err().to_string(), "Service failed");
+}
+
+#[test]
+fn test_service_handle_pause() {
+    let (events_tx, events_rx) = mpsc::channel();
+
+    let service_fn = move |shutdown: Receiver<()>, mut context: ServiceContext<Receiver<()>>| {
+        let pause = context.take_pause_receiver().unwrap();
+        let resume = context.take_resume_receiver().unwrap();
+        loop {
+            if shutdown.recv_timeout(Duration::from_millis(10)).is_ok() {
+                events_tx.send("quitting")?;
+                return Ok(());
+            }
+            if pause.try_recv().is_ok() {
+                events_tx.send("pausing")?;
+            }
+            if resume.try_recv().is_ok() {
+                events_tx.send("resuming")?;
+            }
+        }
+    };
+    let service = BaseService::new_sync("handle_test", service_fn, false).with_pause_channels();
+
+    let handle = spawn_service(service).unwrap();
+    wait_for_state(&handle, ServiceState::Running);
+    assert!(handle.resume().is_err());
+
+    handle.pause().unwrap();
+    assert_eq!(events_rx.recv_timeout(TIMEOUT).unwrap(), "pausing");
+    wait_for_state(&handle, ServiceState::Paused);
+    assert!(handle.pause().is_err());
+    assert!(handle.reload().is_err());
+
+    handle.resume().unwrap();
+    assert_eq!(events_rx.recv_timeout(TIMEOUT).unwrap(), "resuming");
+    wait_for_state(&handle, ServiceState::Running);
+
+    // A paused service can still be stopped
+    handle.pause().unwrap();
+    assert_eq!(events_rx.recv_timeout(TIMEOUT).unwrap(), "pausing");
+    wait_for_state(&handle, ServiceState::Paused);
+    handle.stop();
+    assert_eq!(events_rx.recv_timeout(TIMEOUT).unwrap(), "quitting");
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_service_handle_pause_unsupported() {
+    let service_fn = |shutdown: Receiver<()>, _context| {
+        shutdown.recv()?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("handle_test", service_fn, false);
+
+    let handle = spawn_service(service).unwrap();
+    wait_for_state(&handle, ServiceState::Running);
+    let err = handle.pause().unwrap_err().to_string();
+    assert_eq!(err, "Service 'handle_test' does not support pausing");
+
+    handle.stop();
+    handle.join().unwrap();
+}
+
+#[test]
+fn test_service_handle_command() {
+    let (commands_tx, commands_rx) = mpsc::channel();
+
+    let service_fn = |shutdown: Receiver<()>, _context| {
+        shutdown.recv()?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("handle_test", service_fn, false).with_command_handler(
+        move |command| {
+            commands_tx.send(command)?;
+            Ok(())
+        },
+    );
+
+    let handle = spawn_service(service).unwrap();
+    wait_for_state(&handle, ServiceState::Running);
+    handle.send_command(3).unwrap();
+    handle.send_command(200).unwrap();
+    assert_eq!(commands_rx.recv_timeout(TIMEOUT).unwrap(), 3);
+    assert_eq!(commands_rx.recv_timeout(TIMEOUT).unwrap(), 200);
+
+    handle.stop();
+    wait_for_state(&handle, ServiceState::Stopped);
+    assert!(handle.send_command(3).is_err());
+    handle.join().unwrap();
+}
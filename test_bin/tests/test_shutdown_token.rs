@@ -0,0 +1,103 @@
+use std::{
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use uni_service::{ShutdownReason, ShutdownToken};
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Minimal executor so the async wait can be tested without a runtime
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn test_shutdown_token_clones() {
+    let token = ShutdownToken::new();
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let token = token.clone();
+            thread::spawn(move || token.wait_timeout(WAIT_TIMEOUT))
+        })
+        .collect();
+
+    assert!(!token.is_cancelled());
+    assert_eq!(token.reason(), None);
+    token.cancel();
+    assert!(token.is_cancelled());
+    assert_eq!(token.reason(), Some(ShutdownReason::Programmatic));
+
+    for worker in workers {
+        assert!(worker.join().unwrap());
+    }
+}
+
+#[test]
+fn test_shutdown_token_wait_timeout() {
+    let token = ShutdownToken::new();
+    assert!(!token.wait_timeout(Duration::from_millis(50)));
+}
+
+#[test]
+fn test_shutdown_token_async() {
+    let token = ShutdownToken::new();
+    let cancelled = token.cancelled();
+
+    let canceller = {
+        let token = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        })
+    };
+
+    block_on(cancelled);
+    canceller.join().unwrap();
+    // Already cancelled, so this completes immediately
+    block_on(token.cancelled());
+}
+
+#[test]
+fn test_shutdown_token_children() {
+    let token = ShutdownToken::new();
+    let child = token.child_token();
+    let grandchild = child.child_token();
+    let sibling = token.child_token();
+
+    // Cancelling a child doesn't affect its parent or siblings
+    child.cancel();
+    assert!(child.is_cancelled());
+    assert!(grandchild.is_cancelled());
+    assert!(!token.is_cancelled());
+    assert!(!sibling.is_cancelled());
+
+    token.cancel_with_reason(ShutdownReason::Terminate);
+    assert!(sibling.wait_timeout(WAIT_TIMEOUT));
+    assert_eq!(sibling.reason(), Some(ShutdownReason::Terminate));
+    // The first reason wins
+    assert_eq!(child.reason(), Some(ShutdownReason::Programmatic));
+    // Children of a cancelled token start out cancelled
+    assert!(token.child_token().is_cancelled());
+}
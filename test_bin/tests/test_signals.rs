@@ -0,0 +1,54 @@
+// Signals are sent to the whole test process, so this file has a single test
+#![cfg(unix)]
+
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use uni_service::{BaseService, run_service};
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+fn spawn_run(started_tx: Sender<()>) -> JoinHandle<uni_service::Result<()>> {
+    let service_fn = move |shutdown: Receiver<()>, _context| {
+        started_tx.send(())?;
+        shutdown.recv()?;
+        Ok(())
+    };
+    let service = BaseService::new_sync("signals_test", service_fn, false);
+    thread::spawn(move || run_service(service, false))
+}
+
+fn terminate() {
+    // SAFETY: Sending a signal to our own process has no memory safety implications
+    unsafe {
+        libc::kill(libc::getpid(), libc::SIGTERM);
+    }
+}
+
+#[test]
+fn test_signals_multiple_runs() {
+    // Sequential runs each get the signal
+    for _ in 0..2 {
+        let (started_tx, started_rx) = mpsc::channel();
+        let run = spawn_run(started_tx);
+        started_rx.recv_timeout(TIMEOUT).unwrap();
+
+        terminate();
+        run.join().unwrap().unwrap();
+    }
+
+    // Concurrent runs all get the same signal
+    let (started_tx, started_rx) = mpsc::channel();
+    let runs: Vec<_> = (0..2).map(|_| spawn_run(started_tx.clone())).collect();
+    for _ in 0..2 {
+        started_rx.recv_timeout(TIMEOUT).unwrap();
+    }
+
+    terminate();
+    for run in runs {
+        run.join().unwrap().unwrap();
+    }
+}
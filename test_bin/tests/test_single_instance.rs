@@ -0,0 +1,68 @@
+mod common;
+
+use std::sync::mpsc::Receiver;
+
+use uni_service::{BaseService, ServiceApp, ServiceState, SingleInstance, spawn_service};
+
+use crate::common::handle::wait_for_state;
+
+fn single_instance() -> SingleInstance<impl ServiceApp + Send + 'static> {
+    let service_fn = |shutdown: Receiver<()>, _context| {
+        shutdown.recv()?;
+        Ok(())
+    };
+    let lock_dir = std::env::temp_dir().join(format!("uni_service_lock_{}", std::process::id()));
+    SingleInstance::new(BaseService::new_sync(
+        "single_instance_test",
+        service_fn,
+        false,
+    ))
+    .with_lock_dir(lock_dir)
+}
+
+#[test]
+fn test_single_instance() {
+    let first = single_instance();
+    let lock_path = first.lock_path();
+    let first = spawn_service(first).unwrap();
+    wait_for_state(&first, ServiceState::Running);
+    assert!(lock_path.exists());
+
+    // The second copy refuses to start, naming the process holding the lock
+    let second = spawn_service(single_instance()).unwrap();
+    let err = second.join().unwrap_err().to_string();
+    assert!(err.contains("Service 'single_instance_test' is already running"));
+    assert!(err.contains(&format!("(PID {})", std::process::id())));
+
+    // The lock is released once the first copy stops
+    first.stop();
+    first.join().unwrap();
+    assert!(!lock_path.exists());
+
+    let third = spawn_service(single_instance()).unwrap();
+    wait_for_state(&third, ServiceState::Running);
+    third.stop();
+    third.join().unwrap();
+
+    std::fs::remove_dir_all(lock_path.parent().unwrap()).unwrap();
+}
+
+// The lock file may live in a directory shared with other users, so a planted link must not be followed
+#[cfg(unix)]
+#[test]
+fn test_single_instance_refuses_symlink() {
+    let app = single_instance();
+    let lock_path = app.lock_path();
+    let lock_dir = lock_path.parent().unwrap().with_extension("symlink");
+    let lock_path = lock_dir.join(lock_path.file_name().unwrap());
+    let target = lock_dir.join("target");
+    std::fs::create_dir_all(&lock_dir).unwrap();
+    std::fs::write(&target, "keep").unwrap();
+    std::os::unix::fs::symlink(&target, &lock_path).unwrap();
+
+    let handle = spawn_service(app.with_lock_dir(&lock_dir)).unwrap();
+    assert!(handle.join().is_err());
+    assert_eq!(std::fs::read_to_string(&target).unwrap(), "keep");
+
+    std::fs::remove_dir_all(lock_dir).unwrap();
+}